@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use chrono::Local;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::PkgData;
+
+// A security update, kept locally so that it can be announced later
+#[derive(Serialize)]
+struct Advisory<'a> {
+	package: &'a str,
+	version: &'a str,
+	release: u16,
+	date: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	commit: Option<&'a str>,
+	cves: &'a [String],
+	#[serde(skip_serializing_if = "Option::is_none")]
+	changelog: Option<&'a str>
+}
+
+// Writes <advisory_dir>/<name>-<version>-<release>.yml, commit is the one
+// the update went in
+pub fn write(pkg: &PkgData, commit: Option<&str>, config: &Config) -> Result<PathBuf> {
+	let dir = config.advisory_dir().ok_or_else(|| Error::parse("There's no folder to write advisories to, set advisory_dir"))?;
+	std::fs::create_dir_all(&dir)?;
+
+	let advisory = Advisory {
+		package: &pkg.name,
+		version: &pkg.version,
+		release: pkg.release,
+		date: Local::now().format("%Y-%m-%d").to_string(),
+		commit,
+		cves: &pkg.cves,
+		changelog: pkg.changelog.as_ref().and_then(|changelog| changelog.url.as_deref())
+	};
+
+	let path = dir.join(format!("{}-{}-{}.yml", pkg.name, pkg.version, pkg.release));
+	std::fs::write(&path, serde_yaml::to_string(&advisory)?)?;
+	Ok(path)
+}
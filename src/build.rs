@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::worker::Job;
+
+// How often the build is checked for new output and cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How long a cancelled build has to stop before it's killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+	// Builds in a clean chroot, needs root
+	Solbuild,
+	// Builds on the host, deps have to be installed already
+	Ypkg
+}
+
+impl Tool {
+	pub fn name(self) -> &'static str {
+		match self {
+			Tool::Solbuild => "solbuild",
+			Tool::Ypkg => "ypkg-build"
+		}
+	}
+
+	fn command(self, pkg_file: &str) -> Command {
+		let mut command = match self {
+			// -n: fail instead of asking for a password nobody can type in
+			Tool::Solbuild => {
+				let mut command = Command::new("sudo");
+				command.args(&["-n", "solbuild", "build", pkg_file]);
+				command
+			}
+			Tool::Ypkg => {
+				let mut command = Command::new("fakeroot");
+				command.args(&["ypkg-build", "-D", ".", pkg_file]);
+				command
+			}
+		};
+		command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+		command
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildReport {
+	pub tool: Tool,
+	pub success: bool,
+	// How it ended, "exit code 1"...
+	pub status: String,
+	// Files the build made or changed: .eopkg, pspec_x86_64.xml, abi_*...
+	pub artifacts: Vec<PathBuf>
+}
+
+fn in_path(program: &str) -> bool {
+	std::env::var_os("PATH").map_or(false, |paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+// build_tool in the config wins, solbuild is preferred otherwise
+pub fn find_tool(config: &Config) -> Result<Tool> {
+	match config.build_tool.as_ref().map(|tool| tool.as_str()) {
+		Some("solbuild") => Ok(Tool::Solbuild),
+		Some("ypkg") | Some("ypkg-build") => Ok(Tool::Ypkg),
+		Some(other) => Err(Error::parse(format!("Unknown build_tool \"{}\", use solbuild or ypkg", other))),
+		None if in_path("solbuild") => Ok(Tool::Solbuild),
+		None if in_path("ypkg-build") => Ok(Tool::Ypkg),
+		None => Err(Error::tool("build", "neither solbuild nor ypkg-build are installed"))
+	}
+}
+
+fn snapshot(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+	std::fs::read_dir(dir).map(|entries| {
+		entries.filter_map(|entry| entry.ok())
+			.filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.modified().ok()?)))
+			.filter(|(path, _)| path.is_file())
+			.collect()
+	}).unwrap_or_default()
+}
+
+fn forward_lines<R: Read + Send + 'static>(reader: R, lines: mpsc::Sender<String>) {
+	std::thread::spawn(move || {
+		for line in BufReader::new(reader).lines().filter_map(|line| line.ok()) {
+			if lines.send(line).is_err() {
+				break;
+			}
+		}
+	});
+}
+
+// SIGTERM instead of the SIGKILL of Child::kill: sudo can't pass SIGKILL on,
+// so solbuild (running as root) would keep going
+fn terminate(child: &Child) {
+	let _ = Command::new("kill").arg("-TERM").arg(child.id().to_string()).status();
+}
+
+// Stops a cancelled build, its output keeps going to log until both pipes are
+// closed, which is when the build is really over
+fn stop<L: Fn(String)>(child: &mut Child, receiver: &mpsc::Receiver<String>, log: &L) {
+	terminate(child);
+	let deadline = Instant::now() + STOP_TIMEOUT;
+	loop {
+		match receiver.recv_timeout(POLL_INTERVAL) {
+			Ok(line) => log(line),
+			Err(mpsc::RecvTimeoutError::Disconnected) => break,
+			Err(mpsc::RecvTimeoutError::Timeout) if Instant::now() > deadline => {
+				log("The build didn't stop, killing it".to_string());
+				let _ = child.kill();
+				break;
+			}
+			Err(mpsc::RecvTimeoutError::Timeout) => {}
+		}
+	}
+	let _ = child.wait();
+}
+
+// Builds pkg_file with tool in its directory, every line of output goes to
+// log as it comes. A failed build is still a report, only not being able to
+// run it at all is an error.
+pub fn run<L: Fn(String)>(tool: Tool, pkg_file: &Path, log: L, job: &Job) -> Result<BuildReport> {
+	let dir = pkg_file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+	let file_name = pkg_file.file_name().and_then(|name| name.to_str()).unwrap_or("package.yml");
+	let before = snapshot(dir);
+
+	job.step(&format!("Building with {}", tool.name()));
+	let mut child = tool.command(file_name).current_dir(dir).spawn().map_err(|err| Error::tool(tool.name(), err.to_string()))?;
+
+	let (sender, receiver) = mpsc::channel();
+	forward_lines(child.stdout.take().unwrap(), sender.clone());
+	forward_lines(child.stderr.take().unwrap(), sender);
+
+	let status = loop {
+		if job.check_cancelled().is_err() {
+			job.step("Stopping the build");
+			stop(&mut child, &receiver, &log);
+			return Err(Error::Cancelled);
+		}
+
+		match receiver.recv_timeout(POLL_INTERVAL) {
+			Ok(line) => log(line),
+			Err(mpsc::RecvTimeoutError::Timeout) => {}
+			// Both pipes are closed, the build is over
+			Err(mpsc::RecvTimeoutError::Disconnected) => break child.wait()?
+		}
+	};
+
+	let after = snapshot(dir);
+	let mut artifacts = after.into_iter()
+		.filter(|(path, modified)| before.get(path).map_or(true, |old| old < modified))
+		.map(|(path, _)| path)
+		.collect::<Vec<_>>();
+	artifacts.sort();
+
+	Ok(BuildReport {
+		tool,
+		success: status.success(),
+		status: status.code().map_or("killed by a signal".to_string(), |code| format!("exit code {}", code)),
+		artifacts
+	})
+}
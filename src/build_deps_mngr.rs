@@ -6,6 +6,8 @@ use itertools::Itertools;
 use regex::Regex;
 use edit_distance::edit_distance;
 
+use crate::error::{Error, Result};
+
 
 
 const BLACKLIST: &[&str] = &["meson"];
@@ -23,36 +25,36 @@ fn strip_dep(dep: &str) -> String {
 	reg_strip.captures(dep).unwrap().get(1).unwrap().as_str().to_string()
 }
 
-fn search_on_eopkg(dep: &str) -> Option<String> {
+fn search_on_eopkg(dep: &str) -> Result<Option<String>> {
 	println!("Looking for: {:?}", dep);
     let output = Command::new("eopkg")
         .args(&["search", dep])
         .output()
-        .expect("failed to execute process");
+        .map_err(|err| Error::tool("eopkg", err.to_string()))?;
 
     let out_regex = Regex::new(r"(?m)^(\S+)\s+-").unwrap();
-    let whole_text = console::strip_ansi_codes(std::str::from_utf8(&output.stdout).unwrap()).to_string();
+    let whole_text = console::strip_ansi_codes(&String::from_utf8_lossy(&output.stdout)).to_string();
     let res = whole_text.lines()
-    	.map(|line|out_regex.captures(line).unwrap()
-    		.get(1).unwrap().as_str().to_string())
+    	.filter_map(|line|out_regex.captures(line))
+    	.map(|captures| captures[1].to_string())
     	.map(|pkg_name| (pkg_name.clone(), edit_distance(&pkg_name, dep)))
     	.sorted_by(|(_, dist_a),(_, dist_b)| std::cmp::Ord::cmp(dist_a, dist_b))
     	.map(|(pkg_name, _)| pkg_name)
     	.next();
 
-    res
+    Ok(res)
 }
 
-fn try_search_dep(dep: String) -> String {
-	if let Some(search_res) = search_on_eopkg(&dep) {
-		search_res
+fn try_search_dep(dep: String) -> Result<String> {
+	if let Some(search_res) = search_on_eopkg(&dep)? {
+		Ok(search_res)
 	}
 	else {
-		dep + " (not found in repos)"
+		Ok(dep + " (not found in repos)")
 	}
 }
 
-fn filter_and_trans(dep: &str) -> Option<(String,String)> {
+pub(crate) fn filter_and_trans(dep: &str) -> Option<(String,String)> {
 	let dep = strip_dep(dep);
 	let repl_map = make_replaces_dict();
 	if !BLACKLIST.contains(&dep.as_str()) {
@@ -92,8 +94,8 @@ fn filter_and_trans(dep: &str) -> Option<(String,String)> {
 }
 
 
-// True if modification has been made
-pub fn show_build_deps(deps: &mut Vec<String>, parent: &gtk::Window) -> bool {
+// True if modification has been made, if translation fails deps are left untouched
+pub fn show_build_deps(deps: &mut Vec<String>, parent: &gtk::Window) -> Result<bool> {
 	let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::OkCancel, "Build dependencies");
 	let txt_deps = gtk::TextView::new();
 	txt_deps.set_vexpand(true);
@@ -109,15 +111,18 @@ pub fn show_build_deps(deps: &mut Vec<String>, parent: &gtk::Window) -> bool {
     let resp = dialog.run();
     let new_text = buffer.get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false).unwrap().to_string();
 
+    let translate = should_trans.get_active();
+    dialog.destroy();
+
     if resp == gtk::ResponseType::Ok {
     	*deps = {
     		println!("{:?}", new_text);
-    		if should_trans.get_active() {
+    		if translate {
     			new_text.lines()
     			.filter_map(filter_and_trans)
     			.map(|(_, new_dep)| new_dep)
-    			.map(|dep|if !dep.is_empty(){try_search_dep(dep)} else{dep})
-    			.collect()
+    			.map(|dep|if !dep.is_empty(){try_search_dep(dep)} else{Ok(dep)})
+    			.collect::<Result<_>>()?
     		}
     		else {
     			new_text.lines().map(|dep|dep.to_string()).collect()
@@ -125,7 +130,5 @@ pub fn show_build_deps(deps: &mut Vec<String>, parent: &gtk::Window) -> bool {
     	};
     }
 
-    dialog.destroy();
-
-    org_text != new_text
+    Ok(org_text != new_text)
 }
\ No newline at end of file
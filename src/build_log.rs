@@ -0,0 +1,105 @@
+use gtk::prelude::*;
+use gtk::DialogFlags;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::build_deps_mngr::filter_and_trans;
+
+// What the build looked for and didn't find
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingDep {
+	// As the build system called it
+	pub name: String,
+	// What it most likely is in builddeps
+	pub builddep: String,
+	// The log line that says so
+	pub line: String
+}
+
+// Each of these has the missing name in its first matching group
+static FAILURE_REGEXES: Lazy<Vec<Regex>> = Lazy::new(|| {
+	[
+		// meson
+		r"[Dd]ependency (\S+) found: NO",
+		r"Program (\S+) found: NO",
+		// CMake, with and without a config file
+		r#"Could not find a package configuration file provided by "([^"]+)""#,
+		r"Could (?:not|NOT) find (?:package )?([\w.+-]+)",
+		// pkg-config, pkgconf and PKG_CHECK_MODULES in configure
+		r"No package '([^']+)' found",
+		r"Package '?([\w.+-]+)'?,? (?:was not found in the pkg-config search path|required by .* not found)",
+		// Shells, bash and dash
+		r"(?:^|: )([\w.+-]+): (?:command )?not found"
+	].iter().map(|reg| Regex::new(reg).unwrap()).collect()
+});
+
+// configure checks for lots of things it can do without, "checking for
+// foo... no" only matters when configure gives up right after
+static FAILED_CHECK: Lazy<Regex> = Lazy::new(|| Regex::new(r"checking for ([\w.+-]+)(?: .*?)?\.\.\. no$").unwrap());
+
+// Every missing dependency the log mentions, once, in the order they show up
+pub fn analyze(log: &str) -> Vec<MissingDep> {
+	let mut last_failed: Option<(String, &str)> = None;
+	let mut found: Vec<MissingDep> = Vec::new();
+
+	for line in log.lines().map(|line| line.trim()) {
+		if line.starts_with("checking ") {
+			last_failed = FAILED_CHECK.captures(line).map(|caps| (caps[1].to_string(), line));
+			continue;
+		}
+
+		let (name, line) = match FAILURE_REGEXES.iter().find_map(|reg| reg.captures(line)) {
+			Some(caps) => (caps[1].to_string(), line),
+			// PKG_CHECK_MODULES names the packages in the next lines
+			None if line.starts_with("configure: error:") && !line.contains("Package requirements") => match last_failed.take() {
+				Some(failed) => failed,
+				None => continue
+			},
+			None => continue
+		};
+
+		// Blacklisted ones (meson itself...) aren't worth adding
+		let builddep = match filter_and_trans(&name.to_lowercase()) {
+			Some((_, builddep)) if !builddep.is_empty() => builddep,
+			_ => continue
+		};
+
+		if !found.iter().any(|missing| missing.builddep == builddep) {
+			found.push(MissingDep {name, builddep, line: line.to_string()});
+		}
+	}
+
+	found
+}
+
+// Lets the user pick which of missing to add, the ones already in build_deps
+// are left out. Returns the chosen builddeps.
+pub fn show_missing(parent: &gtk::Window, missing: &[MissingDep], build_deps: &[String]) -> Vec<String> {
+	let missing = missing.iter().filter(|missing| !build_deps.contains(&missing.builddep)).collect::<Vec<_>>();
+	if missing.is_empty() {
+		return Vec::new();
+	}
+
+	let dialog = gtk::MessageDialog::new(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::Cancel, "The build is missing some dependencies");
+	dialog.set_property_secondary_text(Some("Ticked ones are added to builddeps"));
+	dialog.add_button("Add", gtk::ResponseType::Ok);
+	dialog.set_default_response(gtk::ResponseType::Ok);
+
+	let checks = missing.iter().map(|missing| {
+		let check = gtk::CheckButton::new_with_label(&missing.builddep);
+		check.set_active(true);
+		check.set_tooltip_text(Some(&missing.line));
+		dialog.get_content_area().pack_start(&check, false, false, 0);
+		check
+	}).collect::<Vec<_>>();
+
+	dialog.show_all();
+	let response = dialog.run();
+	let chosen = missing.iter().zip(checks.iter())
+		.filter(|(_, check)| check.get_active())
+		.map(|(missing, _)| missing.builddep.clone())
+		.collect();
+	dialog.destroy();
+
+	if response == gtk::ResponseType::Ok {chosen} else {Vec::new()}
+}
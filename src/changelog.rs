@@ -0,0 +1,190 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Url;
+
+use crate::config::Config;
+use crate::metadata::{self, ReleaseNote};
+use crate::version::Version;
+
+// Files in the archive that list what changed, most preferred first
+pub const CHANGELOG_FILES: &[&str] = &["news", "news.md", "changelog.md", "changelog", "changes.md", "changes", "releasenotes.md"];
+// More than this is cut, it's a summary
+const MAX_LINES: usize = 200;
+
+static REG_CVE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bCVE-\d{4}-\d{4,}\b").unwrap());
+static REG_VERSION: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+\.\d+").unwrap());
+
+// What changed upstream between the packaged version and the new one
+#[derive(Debug, Clone)]
+pub struct Changelog {
+	// Where it comes from: "GitHub releases", "NEWS"...
+	pub origin: String,
+	// Page with the release notes, if there's one
+	pub url: Option<String>,
+	pub text: String,
+	// Security issues it mentions
+	pub cves: Vec<String>
+}
+
+impl Changelog {
+	fn new(origin: &str, url: Option<String>, text: String) -> Self {
+		let cves = cve_ids(&text);
+		Changelog {origin: origin.to_string(), url, text, cves}
+	}
+}
+
+// Every CVE id in text, once and sorted
+pub fn cve_ids(text: &str) -> Vec<String> {
+	let mut cves = REG_CVE.find_iter(text).map(|found| found.as_str().to_uppercase()).collect::<Vec<_>>();
+	cves.sort();
+	cves.dedup();
+	cves
+}
+
+// "1.2" is in "Version 1.2:" but not in "1.2.1" or "11.2"
+fn mentions(line: &str, version: &str) -> bool {
+	line.match_indices(version).any(|(pos, _)| {
+		let before = line[..pos].chars().next_back();
+		let mut after = line[pos + version.len()..].chars();
+		let goes_on = match after.next() {
+			Some(c) if c.is_ascii_digit() => true,
+			Some('.') => after.next().map_or(false, |c| c.is_ascii_digit()),
+			_ => false
+		};
+		!before.map_or(false, |c| c.is_ascii_digit() || c == '.') && !goes_on
+	})
+}
+
+// Lines that start the entry of a release: "## [1.2.0]", "Version 1.2.0",
+// "Overview of changes in 1.2.0"... Entries inside a release are indented or
+// bullets, so they don't count.
+fn is_heading(line: &str) -> bool {
+	let starts_entry = line.starts_with('#') || !(line.starts_with(char::is_whitespace) || line.starts_with(|c| c == '-' || c == '*' || c == '+'));
+	starts_entry && line.len() < 100 && REG_VERSION.is_match(line)
+}
+
+// The entries after old up to new (the whole entry of new if old isn't found)
+pub fn section(text: &str, old: Option<&str>, new: &str) -> Option<String> {
+	let lines = text.lines().collect::<Vec<_>>();
+	let start = lines.iter().position(|line| is_heading(line) && mentions(line, new))?;
+	let end = lines[start + 1..].iter()
+		.position(|line| is_heading(line) && old.map_or(true, |old| mentions(line, old)))
+		// Without a heading for old the next one ends the new version's entry
+		.or_else(|| lines[start + 1..].iter().position(|line| is_heading(line)))
+		.map_or(lines.len(), |pos| start + 1 + pos);
+
+	let section = lines[start..end.min(start + MAX_LINES)].join("\n").trim_end().to_string();
+	if section.is_empty() {None} else {Some(section)}
+}
+
+// Tags are "v1.2.0", "foo-1.2.0", "1.2.0"...
+fn tag_is(tag: &str, version: &str) -> bool {
+	let tag = tag.trim_start_matches(|c: char| !c.is_ascii_digit());
+	tag == version || Version::normalise(tag).map_or(false, |tag| tag.to_string() == version)
+}
+
+// Notes of every release after old up to new, newest first
+fn from_releases(notes: Vec<ReleaseNote>, old: &str, new: &str) -> Option<Changelog> {
+	let start = notes.iter().position(|note| tag_is(&note.tag, new))?;
+	let url = notes[start].url.clone();
+	let text = notes[start..].iter()
+		.take_while(|note| !tag_is(&note.tag, old))
+		.filter(|note| !note.body.trim().is_empty())
+		.map(|note| format!("{}\n\n{}", note.tag, note.body.trim()))
+		.collect::<Vec<_>>()
+		.join("\n\n");
+
+	if text.is_empty() {
+		return None;
+	}
+	let text = text.lines().take(MAX_LINES).collect::<Vec<_>>().join("\n");
+	Some(Changelog::new("release notes", url, text))
+}
+
+// Release notes on the forge are preferred over the files in the archive,
+// archive_file is (name, contents)
+pub fn find(url: &Url, config: &Config, old: &str, new: &str, archive_file: Option<(String, String)>) -> Option<Changelog> {
+	let notes = metadata::fetch_release_notes(url, config).ok().and_then(|notes| from_releases(notes, old, new));
+	notes.or_else(|| {
+		let (name, text) = archive_file?;
+		let section = section(&text, Some(old), new)?;
+		Some(Changelog::new(&name, metadata::release_notes_url(url), section))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const NEWS: &str = "Overview of changes in 1.4.0
+============================
+
+* Port to GTK 4
+* Fix CVE-2020-1234
+
+Overview of changes in 1.3.1
+============================
+
+* Fix a crash when 1.2 files are opened
+
+Overview of changes in 1.3.0
+============================
+
+* New icon
+";
+
+	const MARKDOWN: &str = "# Changelog
+
+## [2.0.0] - 2020-05-01
+### Changed
+- Everything
+
+## [1.10.0] - 2020-01-01
+- Something
+
+## [1.1.0] - 2019-06-01
+- First
+";
+
+	#[test]
+	fn mentions_whole_versions() {
+		assert!(mentions("Version 1.2:", "1.2"));
+		assert!(mentions("## [1.2] - 2020-01-01", "1.2"));
+		assert!(mentions("1.2.", "1.2"));
+		assert!(!mentions("1.2.1", "1.2"));
+		assert!(!mentions("11.2", "1.2"));
+		assert!(!mentions("1.21", "1.2"));
+	}
+
+	#[test]
+	fn sections_from_new_to_old() {
+		assert_eq!(section(NEWS, Some("1.3.0"), "1.4.0").unwrap(), NEWS[..NEWS.find("Overview of changes in 1.3.0").unwrap()].trim_end());
+		assert_eq!(section(MARKDOWN, Some("1.10.0"), "2.0.0").unwrap(), "## [2.0.0] - 2020-05-01\n### Changed\n- Everything");
+		// 1.1.0 isn't 1.10.0
+		assert!(section(MARKDOWN, Some("1.1.0"), "2.0.0").unwrap().contains("[1.10.0]"));
+	}
+
+	#[test]
+	fn only_new_without_old() {
+		assert_eq!(section(NEWS, None, "1.3.1").unwrap(), "Overview of changes in 1.3.1\n============================\n\n* Fix a crash when 1.2 files are opened");
+		assert_eq!(section(NEWS, Some("0.9"), "1.3.1"), section(NEWS, None, "1.3.1"));
+		assert_eq!(section(NEWS, Some("1.3.0"), "1.5.0"), None);
+	}
+
+	#[test]
+	fn release_notes_between_tags() {
+		let note = |tag: &str, body: &str| ReleaseNote {tag: tag.to_string(), body: body.to_string(), url: Some(format!("https://example.com/{}", tag))};
+		let notes = vec![note("v1.5.0", "Not yet"), note("v1.4.0", "Fixes CVE-2020-1234"), note("v1.3.1", ""), note("v1.3.0", "Old")];
+
+		let changelog = from_releases(notes.clone(), "1.3.0", "1.4.0").unwrap();
+		assert_eq!(changelog.text, "v1.4.0\n\nFixes CVE-2020-1234");
+		assert_eq!(changelog.url.as_deref(), Some("https://example.com/v1.4.0"));
+		assert_eq!(changelog.cves, ["CVE-2020-1234"]);
+		assert!(from_releases(notes, "1.3.0", "1.3.1").is_none());
+	}
+
+	#[test]
+	fn cves_once_and_sorted() {
+		assert_eq!(cve_ids("cve-2021-0002, CVE-2020-12345 and CVE-2021-0002 again, not CVE-20-1"), ["CVE-2020-12345", "CVE-2021-0002"]);
+	}
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use roxmltree::{Document, Node, NS_XML_URI};
+
+use crate::config::Config;
+use crate::vars::COMPONENTS;
+
+// Where eopkg keeps the index of every repo it knows
+const EOPKG_INDEX_DIR: &str = "/var/lib/eopkg/index";
+
+// Dependencies that give away what kind of software a package is, a
+// dependency containing the first string points to the component
+const COMPONENT_HINTS: &[(&str, &str)] = &[
+	("qt5", "desktop.qt"),
+	("qt6", "desktop.qt"),
+	("kf5", "desktop.kde"),
+	("gtk", "desktop.gtk"),
+	("libadwaita", "desktop.gnome"),
+	("gnome-", "desktop.gnome"),
+	("budgie", "desktop.budgie"),
+	("mate-", "desktop.mate"),
+	("python", "programming.python"),
+	("perl", "programming.perl"),
+	("ruby", "programming.ruby"),
+	("openjdk", "programming.java"),
+	("ghc", "programming.haskell"),
+	("gstreamer", "multimedia.gstreamer"),
+	("alsa", "multimedia.audio"),
+	("pulseaudio", "multimedia.audio"),
+	("ffmpeg", "multimedia.video"),
+	("sdl2", "games"),
+	("xorg-server", "xorg.driver"),
+	("fontconfig", "desktop.font")
+];
+
+// Forge topics that point to a component
+const TOPIC_HINTS: &[(&str, &str)] = &[
+	("game", "games"),
+	("emulator", "games.emulator"),
+	("qt", "desktop.qt"),
+	("gtk", "desktop.gtk"),
+	("gnome", "desktop.gnome"),
+	("kde", "desktop.kde"),
+	("budgie", "desktop.budgie"),
+	("python", "programming.python"),
+	("haskell", "programming.haskell"),
+	("ide", "programming.ide"),
+	("editor", "editor"),
+	("font", "desktop.font"),
+	("icon-theme", "desktop.theme"),
+	("gtk-theme", "desktop.theme"),
+	("audio", "multimedia.audio"),
+	("music", "multimedia.audio"),
+	("video", "multimedia.video"),
+	("graphics", "multimedia.graphics"),
+	("irc", "network.irc"),
+	("email", "network.mail"),
+	("browser", "network.web.browser"),
+	("download", "network.download"),
+	("vpn", "network.util"),
+	("security", "security"),
+	("cryptography", "security.crypto"),
+	("database", "database"),
+	("cli", "system.utils"),
+	("command-line", "system.utils")
+];
+
+// Build files of language specific tools, by the end of their name, that
+// point to a component
+pub const BUILD_FILE_HINTS: &[(&str, &str)] = &[
+	("setup.py", "programming.python"),
+	("pyproject.toml", "programming.python"),
+	("makefile.pl", "programming.perl"),
+	("build.pl", "programming.perl"),
+	(".gemspec", "programming.ruby"),
+	(".cabal", "programming.haskell"),
+	("pom.xml", "programming.java"),
+	("build.gradle", "programming.java")
+];
+
+// How much each kind of hint counts, topics are chosen by upstream so they
+// are the most reliable
+const TOPIC_WEIGHT: usize = 3;
+const DEP_WEIGHT: usize = 2;
+const BUILD_FILE_WEIGHT: usize = 2;
+const FONT_WEIGHT: usize = 3;
+const DESKTOP_FILE_WEIGHT: usize = 1;
+
+// What was found about the package that says where it belongs
+#[derive(Debug, Clone, Default)]
+pub struct Signals {
+	pub topics: Vec<String>,
+	pub has_desktop_file: bool,
+	pub has_fonts: bool,
+	// Endings in BUILD_FILE_HINTS of the build files in the source
+	pub build_files: Vec<String>
+}
+
+#[derive(Debug, Clone)]
+pub struct Component {
+	pub name: String,
+	pub summary: Option<String>
+}
+
+impl Component {
+	// What's shown in the combo
+	pub fn label(&self) -> String {
+		match &self.summary {
+			Some(summary) => format!("{} — {}", self.name, summary),
+			None => self.name.clone()
+		}
+	}
+}
+
+fn is_english(node: &Node) -> bool {
+	node.attribute((NS_XML_URI, "lang")).map_or(true, |lang| lang == "en")
+}
+
+// Both components.xml and the eopkg index have <Component> entries with a
+// <Name> and a <Summary> (or <LocalName>) for every language
+fn parse_components(xml: &str) -> Vec<Component> {
+	let doc = match Document::parse(xml) {
+		Ok(doc) => doc,
+		Err(_) => return Vec::new()
+	};
+
+	doc.descendants()
+		.filter(|node| node.has_tag_name("Component"))
+		.filter_map(|node| {
+			let text_of = |tag: &str| node.children().find(|n| n.has_tag_name(tag) && is_english(n)).and_then(|n| n.text()).map(|text| text.trim().to_string());
+			let name = text_of("Name")?;
+			let summary = text_of("Summary").or_else(|| text_of("LocalName")).filter(|summary| !summary.is_empty());
+			Some(Component {name, summary})
+		})
+		.collect()
+}
+
+fn candidate_files(config: &Config) -> Vec<PathBuf> {
+	let mut files = Vec::new();
+	for dir in config.repo_dir.iter().chain(std::iter::once(&config.packages_root())) {
+		files.push(dir.join("components.xml"));
+		files.push(dir.join("common").join("components.xml"));
+	}
+
+	if let Ok(entries) = std::fs::read_dir(EOPKG_INDEX_DIR) {
+		files.extend(entries.filter_map(|entry| entry.ok()).map(|entry| entry.path().join("eopkg-index.xml")));
+	}
+
+	files
+}
+
+fn load_from(path: &Path) -> Vec<Component> {
+	std::fs::read_to_string(path).map_or(Vec::new(), |xml| parse_components(&xml))
+}
+
+// The list packmak comes with, without descriptions
+pub fn builtin() -> Vec<Component> {
+	COMPONENTS.iter().map(|name| Component {name: name.to_string(), summary: None}).collect()
+}
+
+// The components of the local repo checkout or the eopkg index, the built-in
+// list if neither can be found. The index is big, this is better done away
+// from the GUI.
+pub fn load(config: &Config) -> Vec<Component> {
+	let mut components = candidate_files(config).iter()
+		.filter(|path| path.is_file())
+		.map(|path| load_from(path))
+		.find(|components| !components.is_empty())
+		.unwrap_or_else(builtin);
+
+	components.sort_by(|a, b| a.name.cmp(&b.name));
+	components.dedup_by(|a, b| a.name == b.name);
+	components
+}
+
+pub fn exists(components: &[Component], name: &str) -> bool {
+	components.iter().any(|component| component.name == name)
+}
+
+// Components that fit the package, the most likely first
+pub fn suggest(build_deps: &[String], signals: &Signals, components: &[Component]) -> Vec<String> {
+	let mut scores: HashMap<&str, usize> = HashMap::new();
+	for dep in build_deps.iter().map(|dep| dep.to_lowercase()) {
+		for (hint, component) in COMPONENT_HINTS.iter() {
+			if dep.contains(hint) {
+				*scores.entry(component).or_insert(0) += DEP_WEIGHT;
+			}
+		}
+	}
+
+	for file in signals.build_files.iter() {
+		if let Some((_, component)) = BUILD_FILE_HINTS.iter().find(|(ending, _)| ending == file) {
+			*scores.entry(component).or_insert(0) += BUILD_FILE_WEIGHT;
+		}
+	}
+
+	for topic in signals.topics.iter().map(|topic| topic.to_lowercase()) {
+		for (hint, component) in TOPIC_HINTS.iter() {
+			// "qt5" and "gtk4" count, "quiet" doesn't
+			if topic == *hint || topic.trim_end_matches(|c: char| c.is_ascii_digit()) == *hint || topic.ends_with(&format!("-{}", hint)) {
+				*scores.entry(component).or_insert(0) += TOPIC_WEIGHT;
+			}
+		}
+	}
+
+	if signals.has_fonts {
+		*scores.entry("desktop.font").or_insert(0) += FONT_WEIGHT;
+	}
+
+	// Something with a launcher is a desktop app, if nothing says which kind
+	if signals.has_desktop_file {
+		*scores.entry("desktop").or_insert(0) += DESKTOP_FILE_WEIGHT;
+	}
+
+	let mut ranked = scores.into_iter().filter(|(name, _)| exists(components, name)).collect::<Vec<_>>();
+	ranked.sort_by(|(name_a, score_a), (name_b, score_b)| score_b.cmp(score_a).then(name_a.cmp(name_b)));
+	ranked.into_iter().map(|(name, _)| name.to_string()).collect()
+}
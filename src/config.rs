@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+// User settings, read from ~/.config/packmak/config.yml, every key is optional:
+//
+// github_token: <personal access token, raises the API rate limit>
+// packages_root: <where new packages are saved, next to the executable by default>
+// backup: <true to keep the previous package.yml as package.yml.bak>
+// repo_dir: <local checkout of the Solus packages repository>
+// build_tool: <solbuild or ypkg, whichever is installed by default>
+// advisory_dir: <where security advisories are written, ~/.config/packmak/advisories by default>
+// keyring: <keys trusted to sign sources, gpgv's trustedkeys.kbx by default>
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	pub github_token: Option<String>,
+	pub packages_root: Option<PathBuf>,
+	pub backup: bool,
+	pub repo_dir: Option<PathBuf>,
+	pub build_tool: Option<String>,
+	pub advisory_dir: Option<PathBuf>,
+	pub keyring: Option<PathBuf>
+}
+
+impl Config {
+	pub fn path() -> Option<PathBuf> {
+		dirs::config_dir().map(|dir| dir.join("packmak").join("config.yml"))
+	}
+
+	// A missing file just means defaults, what to do with a broken one is up
+	// to the caller
+	pub fn load() -> Result<Self> {
+		let path = match Self::path() {
+			Some(path) if path.is_file() => path,
+			_ => return Ok(Config::default())
+		};
+
+		let text = std::fs::read_to_string(&path).map_err(|err| Error::parse(format!("Can't read {}: {}", path.display(), err)))?;
+		serde_yaml::from_str(&text).map_err(|err| Error::parse(format!("{} isn't valid: {}", path.display(), err)))
+	}
+
+	pub fn packages_root(&self) -> PathBuf {
+		self.packages_root.clone()
+			.or_else(|| std::env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.to_path_buf())))
+			.unwrap_or_else(|| PathBuf::from("."))
+	}
+
+	pub fn advisory_dir(&self) -> Option<PathBuf> {
+		self.advisory_dir.clone().or_else(|| Self::path().and_then(|path| path.parent().map(|dir| dir.join("advisories"))))
+	}
+
+	// GITHUB_TOKEN in the environment wins over the config file
+	pub fn github_token(&self) -> Option<String> {
+		std::env::var("GITHUB_TOKEN").ok()
+			.or_else(|| self.github_token.clone())
+			.filter(|token| !token.trim().is_empty())
+	}
+}
@@ -0,0 +1,133 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use roxmltree::{Document, Node, NS_XML_URI};
+
+// How many paragraphs of a README are taken, after that it's usually
+// build instructions and such
+const MAX_README_PARAGRAPHS: usize = 3;
+
+static REG_UNDERLINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(=+|-+|~+|\^+|\*+)$").unwrap());
+static REG_LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").unwrap());
+static REG_RST_LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"`([^`<]*?)\s*<[^>]*>`_+").unwrap());
+
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamDescription {
+	pub summary: Option<String>,
+	pub description: Option<String>
+}
+
+fn clean_spaces(text: &str) -> String {
+	text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Translations have xml:lang, the untranslated one is the one we want
+fn is_untranslated(node: &Node) -> bool {
+	node.attribute((NS_XML_URI, "lang")).is_none()
+}
+
+fn node_text(node: &Node) -> String {
+	let text = node.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect::<String>();
+	clean_spaces(&text)
+}
+
+// Summary and description out of an AppStream *.metainfo.xml or *.appdata.xml
+pub fn from_appstream(xml: &str) -> Option<UpstreamDescription> {
+	let doc = Document::parse(xml).ok()?;
+	let component = doc.root_element();
+
+	let summary = component.children()
+		.find(|n| n.has_tag_name("summary") && is_untranslated(n))
+		.map(|n| node_text(&n))
+		.filter(|s| !s.is_empty());
+
+	let description = component.children()
+		.find(|n| n.has_tag_name("description") && is_untranslated(n))
+		.map(|descr| {
+			let mut paragraphs = Vec::new();
+			for child in descr.children().filter(|n| n.is_element() && is_untranslated(n)) {
+				match child.tag_name().name() {
+					"p" => paragraphs.push(node_text(&child)),
+					"ul" | "ol" => {
+						let items = child.children()
+							.filter(|n| n.has_tag_name("li") && is_untranslated(n))
+							.map(|li| "- ".to_string() + &node_text(&li))
+							.collect::<Vec<_>>();
+						paragraphs.push(items.join("\n"));
+					}
+					_ => {}
+				}
+			}
+
+			paragraphs.into_iter().filter(|p| !p.is_empty()).collect::<Vec<_>>().join("\n\n")
+		})
+		.filter(|d| !d.is_empty());
+
+	if summary.is_none() && description.is_none() {
+		None
+	}
+	else {
+		Some(UpstreamDescription {summary, description})
+	}
+}
+
+// reStructuredText and Markdown can underline titles
+fn is_underline(line: &str) -> bool {
+	REG_UNDERLINE.is_match(line)
+}
+
+// Lines that are part of a README but not of its prose
+fn is_readme_noise(line: &str) -> bool {
+	line.starts_with('#') || line.starts_with("![") || line.starts_with("[![") || line.starts_with('<') ||
+	line.starts_with(".. ") || line.starts_with("```") || line.starts_with('|') || line.starts_with(':') ||
+	is_underline(line)
+}
+
+// Remove links, emphasis and code marks from Markdown/reStructuredText
+fn strip_markup(text: &str) -> String {
+	let text = REG_LINK.replace_all(text, "$1");
+	let text = REG_RST_LINK.replace_all(&text, "$1");
+	text.replace("**", "").replace("__", "").replace('`', "")
+}
+
+// First paragraphs of a README, anything before the first paragraph (title,
+// badges...) is skipped and it stops at the next heading
+pub fn from_readme(text: &str) -> Option<UpstreamDescription> {
+	let lines = text.lines().map(|l| l.trim()).collect::<Vec<_>>();
+	let mut paragraphs: Vec<String> = Vec::new();
+	let mut current: Vec<String> = Vec::new();
+
+	for (i, line) in lines.iter().enumerate() {
+		let is_underlined = !line.is_empty() && lines.get(i + 1).map_or(false, |next| is_underline(next));
+		let is_heading = line.starts_with('#') || is_underlined;
+
+		if is_heading && (!paragraphs.is_empty() || !current.is_empty()) {
+			break;
+		}
+
+		if line.is_empty() || is_readme_noise(line) || is_underlined {
+			if !current.is_empty() {
+				paragraphs.push(strip_markup(&current.join(" ")));
+				current.clear();
+				if paragraphs.len() == MAX_README_PARAGRAPHS {
+					break;
+				}
+			}
+		}
+		else {
+			current.push(line.to_string());
+		}
+	}
+
+	if !current.is_empty() && paragraphs.len() < MAX_README_PARAGRAPHS {
+		paragraphs.push(strip_markup(&current.join(" ")));
+	}
+
+	let first = paragraphs.first()?;
+	// The first sentence makes a decent summary
+	let summary = first.split(". ").next().map(|s| s.trim_end_matches('.').to_string());
+
+	Some(UpstreamDescription {
+		summary,
+		description: Some(paragraphs.join("\n\n"))
+	})
+}
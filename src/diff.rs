@@ -0,0 +1,143 @@
+// Lines of context around each change, same as diff -u
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+	Same,
+	Removed,
+	Added
+}
+
+// Line by line diff through the longest common subsequence, package.yml files
+// are small enough for it
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+	let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+	for i in (0..old.len()).rev() {
+		for j in (0..new.len()).rev() {
+			lcs[i][j] = if old[i] == new[j] {
+				lcs[i + 1][j + 1] + 1
+			}
+			else {
+				lcs[i + 1][j].max(lcs[i][j + 1])
+			};
+		}
+	}
+
+	let (mut i, mut j) = (0, 0);
+	let mut ops = Vec::new();
+	while i < old.len() && j < new.len() {
+		if old[i] == new[j] {
+			ops.push((Op::Same, old[i]));
+			i += 1;
+			j += 1;
+		}
+		else if lcs[i + 1][j] >= lcs[i][j + 1] {
+			ops.push((Op::Removed, old[i]));
+			i += 1;
+		}
+		else {
+			ops.push((Op::Added, new[j]));
+			j += 1;
+		}
+	}
+	ops.extend(old[i..].iter().map(|line| (Op::Removed, *line)));
+	ops.extend(new[j..].iter().map(|line| (Op::Added, *line)));
+
+	ops
+}
+
+fn hunk_range(start: usize, len: usize) -> String {
+	// diff -u counts lines from 1, an empty range points to the line before
+	if len == 1 {
+		format!("{}", start + 1)
+	}
+	else if len == 0 {
+		format!("{},0", start)
+	}
+	else {
+		format!("{},{}", start + 1, len)
+	}
+}
+
+// Unified diff from old to new, None when there's nothing different
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+	let old_lines = old.lines().collect::<Vec<_>>();
+	let new_lines = new.lines().collect::<Vec<_>>();
+	let ops = diff_lines(&old_lines, &new_lines);
+
+	let changes = ops.iter().enumerate().filter(|(_, (op, _))| *op != Op::Same).map(|(i, _)| i).collect::<Vec<_>>();
+	if changes.is_empty() {
+		return None;
+	}
+
+	// Changes closer than twice the context go in the same hunk
+	let mut hunks: Vec<(usize, usize)> = Vec::new();
+	for &i in changes.iter() {
+		let start = i.saturating_sub(CONTEXT);
+		let end = (i + CONTEXT + 1).min(ops.len());
+		match hunks.last_mut() {
+			Some(last) if start <= last.1 => last.1 = end,
+			_ => hunks.push((start, end))
+		}
+	}
+
+	let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+	for (start, end) in hunks {
+		// Where the hunk starts on each side
+		let old_start = ops[..start].iter().filter(|(op, _)| *op != Op::Added).count();
+		let new_start = ops[..start].iter().filter(|(op, _)| *op != Op::Removed).count();
+		let old_len = ops[start..end].iter().filter(|(op, _)| *op != Op::Added).count();
+		let new_len = ops[start..end].iter().filter(|(op, _)| *op != Op::Removed).count();
+
+		out += &format!("@@ -{} +{} @@\n", hunk_range(old_start, old_len), hunk_range(new_start, new_len));
+		for (op, line) in ops[start..end].iter() {
+			let mark = match op {
+				Op::Same => ' ',
+				Op::Removed => '-',
+				Op::Added => '+'
+			};
+			out.push(mark);
+			out += line;
+			out.push('\n');
+		}
+	}
+
+	Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// What's after the ---/+++ header
+	fn hunks(old: &str, new: &str) -> String {
+		let diff = unified(old, new, "a/package.yml", "b/package.yml").unwrap();
+		assert!(diff.starts_with("--- a/package.yml\n+++ b/package.yml\n"));
+		diff.splitn(3, '\n').nth(2).unwrap().to_string()
+	}
+
+	#[test]
+	fn nothing_changed() {
+		assert_eq!(unified("a\nb\n", "a\nb\n", "a", "b"), None);
+		assert_eq!(unified("", "", "a", "b"), None);
+	}
+
+	// All of these are what diff -u says
+	#[test]
+	fn far_changes_get_their_own_hunk() {
+		let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+		let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn\n";
+		assert_eq!(hunks(old, new), "@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -11,3 +11,4 @@\n k\n l\n m\n+n\n");
+	}
+
+	#[test]
+	fn close_changes_share_a_hunk() {
+		assert_eq!(hunks("a\nb\nc\nd\n", "a\nc\nd\ne\n"), "@@ -1,4 +1,4 @@\n a\n-b\n c\n d\n+e\n");
+	}
+
+	#[test]
+	fn from_and_to_nothing() {
+		assert_eq!(hunks("", "x\n"), "@@ -0,0 +1 @@\n+x\n");
+		assert_eq!(hunks("x\n", ""), "@@ -1 +0,0 @@\n-x\n");
+	}
+}
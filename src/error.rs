@@ -0,0 +1,92 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::metadata::MetadataError;
+
+#[derive(Debug)]
+pub enum Error {
+	Network(reqwest::Error),
+	Metadata(MetadataError),
+	// Something given by the user or found upstream that doesn't make sense
+	Parse(String),
+	Yaml(serde_yaml::Error),
+	Io(std::io::Error),
+	Archive(zip::result::ZipError),
+	// An external program (git, eopkg...) couldn't be run or failed
+	Tool {tool: String, message: String},
+	// Saving would overwrite a file that isn't the one that was loaded
+	ChangedOnDisk(PathBuf),
+	// The user stopped it
+	Cancelled
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+	pub fn parse<S: Into<String>>(message: S) -> Self {
+		Error::Parse(message.into())
+	}
+
+	pub fn tool<S: Into<String>>(tool: &str, message: S) -> Self {
+		Error::Tool {tool: tool.to_string(), message: message.into()}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Network(err) => write!(f, "Network error: {}", err),
+			Error::Metadata(err) => write!(f, "Couldn't get the project data from upstream: {}", err),
+			Error::Parse(msg) => write!(f, "{}", msg),
+			Error::Yaml(err) => write!(f, "Invalid package.yml: {}", err),
+			Error::Io(err) => write!(f, "{}", err),
+			Error::Archive(err) => write!(f, "Couldn't read the archive: {}", err),
+			Error::Tool {tool, message} => write!(f, "{} failed: {}", tool, message),
+			Error::ChangedOnDisk(path) => write!(f, "{} isn't what was loaded, it was changed or created by someone else, use \"Save As\" to save somewhere else", path.display()),
+			Error::Cancelled => write!(f, "Cancelled")
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Error::Network(err) => Some(err),
+			Error::Metadata(err) => Some(err),
+			Error::Yaml(err) => Some(err),
+			Error::Io(err) => Some(err),
+			Error::Archive(err) => Some(err),
+			Error::Parse(_) | Error::Tool {..} | Error::ChangedOnDisk(_) | Error::Cancelled => None
+		}
+	}
+}
+
+impl From<reqwest::Error> for Error {
+	fn from(err: reqwest::Error) -> Self {
+		Error::Network(err)
+	}
+}
+
+impl From<MetadataError> for Error {
+	fn from(err: MetadataError) -> Self {
+		Error::Metadata(err)
+	}
+}
+
+impl From<serde_yaml::Error> for Error {
+	fn from(err: serde_yaml::Error) -> Self {
+		Error::Yaml(err)
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(err: std::io::Error) -> Self {
+		Error::Io(err)
+	}
+}
+
+impl From<zip::result::ZipError> for Error {
+	fn from(err: zip::result::ZipError) -> Self {
+		Error::Archive(err)
+	}
+}
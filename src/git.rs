@@ -0,0 +1,163 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{Error, Result};
+use crate::PkgData;
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+	let output = Command::new("git").current_dir(dir).args(args).output().map_err(|err| Error::tool("git", err.to_string()))?;
+	if output.status.success() {
+		Ok(String::from_utf8_lossy(&output.stdout).to_string())
+	}
+	else {
+		Err(Error::tool("git", String::from_utf8_lossy(&output.stderr).trim().to_string()))
+	}
+}
+
+// The top of the repository dir is in, if it's in one
+pub fn repo_root(dir: &Path) -> Option<PathBuf> {
+	git(dir, &["rev-parse", "--show-toplevel"]).ok().map(|root| PathBuf::from(root.trim()))
+}
+
+// file as it is in the last commit
+pub fn committed_file(file: &Path) -> Option<String> {
+	let dir = file.parent()?;
+	let file_name = file.file_name()?.to_str()?;
+	git(dir, &["show", &format!("HEAD:./{}", file_name)]).ok()
+}
+
+// What changed in dir since the last commit, new files included
+pub fn diff(dir: &Path) -> Result<String> {
+	let mut diff = git(dir, &["diff", "--no-color", "HEAD", "--", "."])
+		// A repo without commits yet has no HEAD
+		.or_else(|_| git(dir, &["diff", "--no-color", "--", "."]))?;
+
+	let untracked = git(dir, &["ls-files", "--others", "--exclude-standard", "--", "."])?;
+	for file in untracked.lines() {
+		diff.push_str(&format!("New file: {}\n", file));
+	}
+	Ok(diff)
+}
+
+// The commit that was just made
+pub fn head(dir: &Path) -> Result<String> {
+	git(dir, &["rev-parse", "HEAD"]).map(|hash| hash.trim().to_string())
+}
+
+// Commits everything in dir and nothing else
+pub fn commit(dir: &Path, message: &str) -> Result<()> {
+	git(dir, &["add", "--all", "--", "."])?;
+	git(dir, &["commit", "--quiet", "-m", message, "--", "."])?;
+	Ok(())
+}
+
+fn added(from: &[String], to: &[String]) -> Vec<String> {
+	to.iter().filter(|dep| !from.contains(dep)).cloned().collect()
+}
+
+// Upstream changes past this are left for the link
+const MAX_CHANGELOG_LINES: usize = 30;
+
+// A message like Solus packagers write, old is what was committed before
+// (None for a new package)
+pub fn commit_message(old: Option<&PkgData>, new: &PkgData, notes_url: Option<&str>) -> String {
+	let changelog = new.changelog.as_ref();
+	let notes_url = changelog.and_then(|changelog| changelog.url.as_deref()).or(notes_url);
+
+	let mut body = Vec::new();
+	let subject = match old {
+		None => format!("{}: Initial inclusion", new.name),
+		Some(old) => {
+			if old.release != new.release && old.version == new.version {
+				body.push(format!("- Release bumped to {}", new.release));
+			}
+
+			let new_deps = added(&old.build_deps, &new.build_deps);
+			if !new_deps.is_empty() {
+				body.push(format!("- New builddeps: {}", new_deps.join(", ")));
+			}
+			let gone_deps = added(&new.build_deps, &old.build_deps);
+			if !gone_deps.is_empty() {
+				body.push(format!("- Removed builddeps: {}", gone_deps.join(", ")));
+			}
+
+			if old.version != new.version {
+				format!("{}: Update to {}", new.name, new.version)
+			}
+			else if old.release != new.release {
+				format!("{}: Rebuild", new.name)
+			}
+			else {
+				format!("{}: Update packaging", new.name)
+			}
+		}
+	};
+
+	let mut message = subject;
+	if !body.is_empty() {
+		message.push_str("\n\n");
+		message.push_str(&body.join("\n"));
+	}
+	if !new.cves.is_empty() {
+		message.push_str(&format!("\n\nSecurity fixes: {}", new.cves.join(", ")));
+	}
+	if let Some(changelog) = changelog {
+		let lines = changelog.text.lines().collect::<Vec<_>>();
+		message.push_str(&format!("\n\nUpstream changes:\n\n{}", lines[..lines.len().min(MAX_CHANGELOG_LINES)].join("\n")));
+		if lines.len() > MAX_CHANGELOG_LINES {
+			message.push_str("\n[...]");
+		}
+	}
+	if let Some(url) = notes_url {
+		message.push_str(&format!("\n\nChangelog: {}", url));
+	}
+	message
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::changelog::Changelog;
+
+	fn pkg(version: &str, release: u16, build_deps: &[&str]) -> PkgData {
+		let mut pkg = PkgData::new();
+		pkg.name = "foo".to_string();
+		pkg.version = version.to_string();
+		pkg.release = release;
+		pkg.build_deps = build_deps.iter().map(|dep| dep.to_string()).collect();
+		pkg
+	}
+
+	#[test]
+	fn subjects() {
+		let old = pkg("1.0", 3, &[]);
+		assert_eq!(commit_message(None, &old, None), "foo: Initial inclusion");
+		assert_eq!(commit_message(Some(&old), &pkg("1.1", 4, &[]), None), "foo: Update to 1.1");
+		assert_eq!(commit_message(Some(&old), &pkg("1.0", 4, &[]), None), "foo: Rebuild\n\n- Release bumped to 4");
+		assert_eq!(commit_message(Some(&old), &pkg("1.0", 3, &[]), None), "foo: Update packaging");
+	}
+
+	#[test]
+	fn builddeps_and_cves() {
+		let old = pkg("1.0", 3, &["pkgconfig(zlib)", "intltool"]);
+		let mut new = pkg("1.1", 4, &["pkgconfig(zlib)", "pkgconfig(gtk+-3.0)"]);
+		new.cves = vec!["CVE-2020-1234".to_string(), "CVE-2020-5678".to_string()];
+		assert_eq!(commit_message(Some(&old), &new, Some("https://example.com/news")),
+			"foo: Update to 1.1\n\n- New builddeps: pkgconfig(gtk+-3.0)\n- Removed builddeps: intltool\n\n\
+			Security fixes: CVE-2020-1234, CVE-2020-5678\n\nChangelog: https://example.com/news");
+	}
+
+	#[test]
+	fn long_changelogs_are_cut() {
+		let mut new = pkg("1.1", 4, &[]);
+		let text = (1..=MAX_CHANGELOG_LINES + 5).map(|line| format!("- Change {}", line)).collect::<Vec<_>>().join("\n");
+		new.changelog = Some(Changelog {origin: "NEWS".to_string(), url: Some("https://example.com/1.1".to_string()), text, cves: Vec::new()});
+
+		let message = commit_message(Some(&pkg("1.0", 3, &[])), &new, Some("https://example.com/news"));
+		assert!(message.starts_with("foo: Update to 1.1\n\nUpstream changes:\n\n- Change 1\n"));
+		assert!(message.contains(&format!("- Change {}\n[...]", MAX_CHANGELOG_LINES)));
+		assert!(!message.contains(&format!("- Change {}\n", MAX_CHANGELOG_LINES + 1)));
+		// The changelog's own page wins
+		assert!(message.ends_with("\n\nChangelog: https://example.com/1.1"));
+	}
+}
@@ -6,6 +6,12 @@ use edit_distance::edit_distance;
 use zip::read::{ZipArchive};
 use reqwest::Url;
 
+use crate::changelog::CHANGELOG_FILES;
+use crate::components::BUILD_FILE_HINTS;
+use crate::description::{self, UpstreamDescription};
+use crate::error::Result;
+use crate::worker::Job;
+
 
 
 const MAX_DIST: usize = 20;
@@ -35,8 +41,11 @@ fn guess_license_str(input: &str) -> String {
 	];
 
 	for (l_name, snippet) in licenses.iter() {
-		if edit_distance(&input[..snippet.len()], snippet) < MAX_DIST {
-			return l_name.to_string()
+		// Shorter files (or cutting in the middle of a char) can't be this license
+		if let Some(input_start) = input.get(..snippet.len()) {
+			if edit_distance(input_start, snippet) < MAX_DIST {
+				return l_name.to_string()
+			}
 		}
 	}
 	
@@ -68,10 +77,106 @@ impl<'a, R: Read + Seek> Gat<'a> for ZipArchive<R> {
 	}
 }
 
+// Tarballs can't be read in any order, so the files are read once and their
+// names kept. Only the text of what can be a README, license, changelog or
+// AppStream file is kept too: small files at the top of the tree, and XML.
+struct TarArchive {
+	files: Vec<(String, Option<Vec<u8>>)>
+}
+
+// Bigger than any README or license, smaller than most other things
+const MAX_TAR_TEXT: u64 = 1024 * 1024;
+
+impl TarArchive {
+	fn new<R: Read>(reader: R) -> Result<Self> {
+		let mut archive = tar::Archive::new(reader);
+		let mut files = Vec::new();
+		for entry in archive.entries()? {
+			let mut entry = entry?;
+			if !entry.header().entry_type().is_file() {
+				continue;
+			}
+			let path = entry.path()?.to_path_buf();
+			let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+			// "foo-1.0/README" is at the top
+			let at_top = path.components().count() <= 2;
+			let is_xml = name.ends_with(".xml") || name.ends_with(".xml.in");
+			let contents = if (at_top || is_xml) && entry.size() <= MAX_TAR_TEXT {
+				let mut contents = Vec::new();
+				entry.read_to_end(&mut contents)?;
+				Some(contents)
+			}
+			else {
+				None
+			};
+			files.push((name, contents));
+		}
+		Ok(TarArchive {files})
+	}
+
+	// The tarball in data, uncompressed as its name says
+	fn open(file_name: &str, data: &[u8]) -> Result<Option<Self>> {
+		let name = file_name.to_lowercase();
+		let tar = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+			TarArchive::new(flate2::read::GzDecoder::new(data))?
+		}
+		else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+			TarArchive::new(xz2::read::XzDecoder::new(data))?
+		}
+		else if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") {
+			TarArchive::new(bzip2::read::BzDecoder::new(data))?
+		}
+		else if name.ends_with(".tar") {
+			TarArchive::new(data)?
+		}
+		else {
+			return Ok(None);
+		};
+		Ok(Some(tar))
+	}
+
+	// The ones whose text was kept win, tarballs can list foo/3rdparty/LICENSE
+	// before foo/LICENSE
+	fn position<P: Fn(&str) -> bool>(&self, pred: P) -> Option<usize> {
+		self.files.iter().enumerate()
+			.filter(|(_, (name, _))| pred(&name.to_lowercase()))
+			.min_by_key(|(_, (_, contents))| contents.is_none())
+			.map(|(i, _)| i)
+	}
+}
+
+impl<'a> Gat<'a> for TarArchive {
+	type FileRead = &'a [u8];
+
+	fn get_file(&'a mut self, id: ArchRef<Self>) -> Option<Self::FileRead> {
+		self.files.get(id)?.1.as_deref()
+	}
+}
+
+impl Archive for TarArchive {
+	type ArchiveRef = usize;
+
+	// The first one like names, preferring the top of the tree (see position)
+	fn search_like(&mut self, names: &[&str]) -> Option<Self::ArchiveRef> {
+		self.position(|file_name| names.contains(&file_name))
+	}
+
+	fn search_ending(&mut self, suffixes: &[&str]) -> Option<Self::ArchiveRef> {
+		self.position(|file_name| suffixes.iter().any(|suffix| file_name.ends_with(suffix)))
+	}
+
+	fn file_name(&mut self, file: Self::ArchiveRef) -> Option<String> {
+		self.files.get(file).map(|(name, _)| name.clone())
+	}
+}
+
 trait Archive {
-	type ArchiveRef;
+	type ArchiveRef: Copy;
 
 	fn search_like(& mut self, names: &[&str]) -> Option<Self::ArchiveRef>;
+	fn search_ending(&mut self, suffixes: &[&str]) -> Option<Self::ArchiveRef>;
+	// Without the folders it's in
+	fn file_name(&mut self, file: Self::ArchiveRef) -> Option<String>;
 }
 
 impl<R: Read + Seek> Archive for ZipArchive<R> {
@@ -83,8 +188,11 @@ impl<R: Read + Seek> Archive for ZipArchive<R> {
 		let max = self.len();
 		let mut index_opt: Option<usize> = None;
 		for i in 0..max {
-			let zip_file = self.by_index(i).unwrap();
-			let file_name = zip_file.sanitized_name().file_name().unwrap().to_str().unwrap().to_lowercase();
+			let zip_file = match self.by_index(i) {
+				Ok(zip_file) => zip_file,
+				Err(_) => continue
+			};
+			let file_name = std::path::Path::new(zip_file.name()).file_name().and_then(|n| n.to_str()).unwrap_or("").to_lowercase();
 			for name in names.iter() {
 				if name == &file_name {
 					index_opt = Some(i)
@@ -100,14 +208,36 @@ impl<R: Read + Seek> Archive for ZipArchive<R> {
 			None
 		}
 	}
+
+	// Same as search_like, but for names ending with one of suffixes
+	// NOTE: suffixes must be in lowercase
+	fn search_ending(&mut self, suffixes: &[&str]) -> Option<Self::ArchiveRef> {
+		for i in 0..self.len() {
+			let zip_file = match self.by_index(i) {
+				Ok(zip_file) => zip_file,
+				Err(_) => continue
+			};
+			let file_name = std::path::Path::new(zip_file.name()).file_name().and_then(|n| n.to_str()).unwrap_or("").to_lowercase();
+			if suffixes.iter().any(|suffix| file_name.ends_with(suffix)) {
+				return Some(i);
+			}
+		}
+
+		None
+	}
+
+	fn file_name(&mut self, file: Self::ArchiveRef) -> Option<String> {
+		let zip_file = self.by_index(file).ok()?;
+		std::path::Path::new(zip_file.name()).file_name().and_then(|n| n.to_str()).map(|n| n.to_string())
+	}
 }
 
-fn guess_license_from_archive_file<R: Read>(input: &mut R) -> String {
+fn guess_license_from_archive_file<R: Read>(input: &mut R) -> Option<String> {
 
 	let mut license_str = String::new();
-	input.read_to_string(&mut license_str).unwrap();
+	input.read_to_string(&mut license_str).ok()?;
 
-	guess_license_str(&license_str)
+	Some(guess_license_str(&license_str))
 }
 
 fn guess_license_from_archive<'a, A: Archive + Gat<'a>>(mut pkg_zip: &'a mut A) -> Option<String> {
@@ -116,7 +246,7 @@ fn guess_license_from_archive<'a, A: Archive + Gat<'a>>(mut pkg_zip: &'a mut A)
 		b1.search_like(&["license", "copying"])
 	};
 	if let Some(license_file) = a {
-		Some(guess_license_from_archive_file::<A::FileRead>(&mut pkg_zip.get_file(license_file).unwrap()))
+		guess_license_from_archive_file::<A::FileRead>(&mut pkg_zip.get_file(license_file)?)
 	}
 	else {
 		None
@@ -124,7 +254,29 @@ fn guess_license_from_archive<'a, A: Archive + Gat<'a>>(mut pkg_zip: &'a mut A)
 
 }
 
-fn guess_build_sys_from_zip<'a, A: Archive>(pkg_zip: &'a mut A) -> Option<String> {
+fn read_archive_file<A>(archive: &mut A, file: A::ArchiveRef) -> Option<String> where A: Archive + for<'a> Gat<'a> {
+	let mut text = String::new();
+	archive.get_file(file)?.read_to_string(&mut text).ok()?;
+	Some(text)
+}
+
+fn guess_appstream_from_archive<A>(archive: &mut A) -> Option<UpstreamDescription> where A: Archive + for<'a> Gat<'a> {
+	let file = archive.search_ending(&[".metainfo.xml", ".appdata.xml", ".metainfo.xml.in", ".appdata.xml.in"])?;
+	description::from_appstream(&read_archive_file(archive, file)?)
+}
+
+fn guess_readme_from_archive<A>(archive: &mut A) -> Option<UpstreamDescription> where A: Archive + for<'a> Gat<'a> {
+	let file = archive.search_like(&["readme.md", "readme", "readme.rst", "readme.txt"])?;
+	description::from_readme(&read_archive_file(archive, file)?)
+}
+
+fn guess_changelog_from_archive<A>(archive: &mut A) -> Option<(String, String)> where A: Archive + for<'a> Gat<'a> {
+	let file = archive.search_like(CHANGELOG_FILES)?;
+	let name = archive.file_name(file)?;
+	Some((name, read_archive_file(archive, file)?))
+}
+
+fn guess_build_sys_from_archive<A: Archive>(pkg_zip: &mut A) -> Option<String> {
 	if let Some(_) = pkg_zip.search_like(&["meson"]) {
 		Some("Meson".to_string())
 	}
@@ -139,21 +291,52 @@ fn guess_build_sys_from_zip<'a, A: Archive>(pkg_zip: &'a mut A) -> Option<String
 	}
 }
 
-pub fn try_guess_license_build_sys_for(url: &Url) -> (Option<String>, Option<String>, Option<Vec<u8>>) {
-	let mut buffer = Vec::new();
-
+// What could be learned from the source itself
+#[derive(Default)]
+pub struct SourceGuess {
+	pub license: Option<String>,
+	pub build_sys: Option<String>,
+	pub appstream: Option<UpstreamDescription>,
+	pub readme: Option<UpstreamDescription>,
+	// Launchers and fonts tell what kind of package it is
+	pub has_desktop_file: bool,
+	pub has_fonts: bool,
+	// Build files of Python, Perl... tools, as in BUILD_FILE_HINTS
+	pub build_files: Vec<String>,
+	// NEWS, CHANGELOG.md... as (name, contents)
+	pub changelog: Option<(String, String)>,
+	pub tarball_data: Option<Vec<u8>>
+}
 
-	let filename = url.path_segments().unwrap().last().unwrap();
-	reqwest::blocking::get(url.as_str()).unwrap().copy_to(&mut buffer).unwrap();
-	let ext = std::path::Path::new(filename).extension().unwrap().to_str().unwrap();
-	let mut cursor = std::io::Cursor::new(buffer.clone());
-	
+pub fn try_guess_from_source(url: &Url, job: &Job) -> Result<SourceGuess> {
+	let filename = url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or("");
+	let buffer = job.download(url.as_str())?;
+	job.step("Looking inside the archive");
+	let ext = std::path::Path::new(filename).extension().and_then(|ext| ext.to_str()).unwrap_or("");
 
-	match ext {
+	let guess = match ext {
 		"zip" => {
-			let mut pkg_zip = zip::read::ZipArchive::new(&mut cursor).unwrap();
-			(guess_license_from_archive(&mut pkg_zip), guess_build_sys_from_zip(&mut pkg_zip), Some(buffer))
+			let mut pkg_zip = zip::read::ZipArchive::new(std::io::Cursor::new(&buffer[..]))?;
+			guess_from_archive(&mut pkg_zip)
 		}
-		_ => (None, None, None)
+		_ => match TarArchive::open(filename, &buffer)? {
+			Some(mut tar) => guess_from_archive(&mut tar),
+			None => SourceGuess::default()
+		}
+	};
+	Ok(SourceGuess {tarball_data: Some(buffer), ..guess})
+}
+
+fn guess_from_archive<A>(archive: &mut A) -> SourceGuess where A: Archive + for<'a> Gat<'a> {
+	SourceGuess {
+		license: guess_license_from_archive(archive),
+		build_sys: guess_build_sys_from_archive(archive),
+		appstream: guess_appstream_from_archive(archive),
+		readme: guess_readme_from_archive(archive),
+		has_desktop_file: archive.search_ending(&[".desktop", ".desktop.in"]).is_some(),
+		has_fonts: archive.search_ending(&[".ttf", ".otf", ".woff2"]).is_some(),
+		build_files: BUILD_FILE_HINTS.iter().map(|(ending, _)| *ending).filter(|ending| archive.search_ending(&[ending]).is_some()).map(String::from).collect(),
+		changelog: guess_changelog_from_archive(archive),
+		tarball_data: None
 	}
-}
\ No newline at end of file
+}
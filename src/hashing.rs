@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use blake2::Blake2b;
+use sha2::{Digest, Sha256, Sha512};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+	Sha256,
+	Sha512,
+	// BLAKE2b with its full 512 bits
+	Blake2b
+}
+
+impl Algorithm {
+	pub fn name(self) -> &'static str {
+		match self {
+			Algorithm::Sha256 => "SHA-256",
+			Algorithm::Sha512 => "SHA-512",
+			Algorithm::Blake2b => "BLAKE2b"
+		}
+	}
+
+	// Length of its hashes in hex
+	pub fn hex_len(self) -> usize {
+		match self {
+			Algorithm::Sha256 => 64,
+			Algorithm::Sha512 | Algorithm::Blake2b => 128
+		}
+	}
+}
+
+enum State {
+	Sha256(Sha256),
+	Sha512(Sha512),
+	Blake2b(Blake2b)
+}
+
+// Takes the data bit by bit, so that it never has to be all in memory
+pub struct Hasher {
+	state: State
+}
+
+impl Hasher {
+	pub fn new(algorithm: Algorithm) -> Self {
+		let state = match algorithm {
+			Algorithm::Sha256 => State::Sha256(Sha256::new()),
+			Algorithm::Sha512 => State::Sha512(Sha512::new()),
+			Algorithm::Blake2b => State::Blake2b(Blake2b::new())
+		};
+		Hasher {state}
+	}
+
+	pub fn update(&mut self, data: &[u8]) {
+		match &mut self.state {
+			State::Sha256(state) => state.update(data),
+			State::Sha512(state) => state.update(data),
+			State::Blake2b(state) => state.update(data)
+		}
+	}
+
+	// The hash as lowercase hex, like sha256sum prints it
+	pub fn finish(self) -> String {
+		match self.state {
+			State::Sha256(state) => hex::encode(state.finalize()),
+			State::Sha512(state) => hex::encode(state.finalize()),
+			State::Blake2b(state) => hex::encode(state.finalize())
+		}
+	}
+}
+
+impl Write for Hasher {
+	fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+		self.update(data);
+		Ok(data.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// Hashes whatever is written through it on its way to inner
+pub struct HashingWriter<W: Write> {
+	inner: W,
+	hasher: Hasher
+}
+
+impl<W: Write> HashingWriter<W> {
+	pub fn new(algorithm: Algorithm, inner: W) -> Self {
+		HashingWriter {inner, hasher: Hasher::new(algorithm)}
+	}
+
+	// The hash of everything written and inner back
+	pub fn finish(self) -> (String, W) {
+		(self.hasher.finish(), self.inner)
+	}
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+	fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+		let written = self.inner.write(data)?;
+		self.hasher.update(&data[..written]);
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+pub fn hash(algorithm: Algorithm, data: &[u8]) -> String {
+	let mut hasher = Hasher::new(algorithm);
+	hasher.update(data);
+	hasher.finish()
+}
+
+pub fn hash_reader<R: Read>(algorithm: Algorithm, mut reader: R) -> io::Result<String> {
+	let mut hasher = Hasher::new(algorithm);
+	let mut chunk = vec![0; CHUNK_SIZE];
+	loop {
+		let read = match reader.read(&mut chunk) {
+			Ok(0) => break,
+			Ok(read) => read,
+			Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+			Err(err) => return Err(err)
+		};
+		hasher.update(&chunk[..read]);
+	}
+	Ok(hasher.finish())
+}
+
+pub fn hash_file(algorithm: Algorithm, path: &Path) -> io::Result<String> {
+	hash_reader(algorithm, File::open(path)?)
+}
+
+// What goes into package.yml
+pub fn sha256(data: &[u8]) -> String {
+	hash(Algorithm::Sha256, data)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// From FIPS 180-2 and RFC 7693
+	const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+	const ABC_SHA512: &str = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+	const ABC_BLAKE2B: &str = "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923";
+
+	#[test]
+	fn known_answers() {
+		assert_eq!(sha256(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+		assert_eq!(sha256(b"abc"), ABC_SHA256);
+		assert_eq!(hash(Algorithm::Sha512, b"abc"), ABC_SHA512);
+		assert_eq!(hash(Algorithm::Blake2b, b"abc"), ABC_BLAKE2B);
+	}
+
+	#[test]
+	fn hex_len_matches() {
+		for algorithm in [Algorithm::Sha256, Algorithm::Sha512, Algorithm::Blake2b].iter() {
+			assert_eq!(hash(*algorithm, b"").len(), algorithm.hex_len());
+		}
+	}
+
+	#[test]
+	fn incremental_is_the_same() {
+		for (algorithm, expected) in [(Algorithm::Sha256, ABC_SHA256), (Algorithm::Sha512, ABC_SHA512), (Algorithm::Blake2b, ABC_BLAKE2B)].iter() {
+			let mut hasher = Hasher::new(*algorithm);
+			hasher.update(b"a");
+			hasher.update(b"");
+			hasher.update(b"bc");
+			assert_eq!(&hasher.finish(), expected);
+		}
+	}
+
+	#[test]
+	fn reader_over_many_chunks() {
+		// A million "a", more than a chunk
+		let data = vec![b'a'; 1_000_000];
+		assert_eq!(hash_reader(Algorithm::Sha256, &data[..]).unwrap(), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+	}
+
+	#[test]
+	fn writer_passes_data_through() {
+		let mut writer = HashingWriter::new(Algorithm::Sha256, Vec::new());
+		writer.write_all(b"abc").unwrap();
+		let (hash, inner) = writer.finish();
+		assert_eq!(hash, ABC_SHA256);
+		assert_eq!(inner, b"abc");
+	}
+}
@@ -0,0 +1,58 @@
+// How many steps can be undone, older ones are forgotten
+const MAX_STEPS: usize = 100;
+
+// Undo/redo over snapshots of the whole state
+pub struct History<T: Clone> {
+	undo: Vec<T>,
+	redo: Vec<T>,
+	// What the last recorded edit was, so that typing a word in the same field
+	// is one step and not one per letter
+	last_edit: Option<&'static str>
+}
+
+impl<T: Clone> History<T> {
+	pub fn new() -> Self {
+		History {undo: Vec::new(), redo: Vec::new(), last_edit: None}
+	}
+
+	// Call with the state as it is right before an edit. Edits of the same kind
+	// one after the other are joined, None never joins.
+	pub fn record(&mut self, before: &T, edit: Option<&'static str>) {
+		if edit.is_none() || edit != self.last_edit {
+			self.undo.push(before.clone());
+			if self.undo.len() > MAX_STEPS {
+				self.undo.remove(0);
+			}
+		}
+
+		self.last_edit = edit;
+		self.redo.clear();
+	}
+
+	// Gives back the state to go to, current is kept so it can be redone
+	pub fn undo(&mut self, current: T) -> Option<T> {
+		let prev = self.undo.pop()?;
+		self.redo.push(current);
+		self.last_edit = None;
+		Some(prev)
+	}
+
+	pub fn redo(&mut self, current: T) -> Option<T> {
+		let next = self.redo.pop()?;
+		self.undo.push(current);
+		self.last_edit = None;
+		Some(next)
+	}
+
+	// Nothing was edited since it was cleared (or the edits were undone)
+	pub fn can_undo(&self) -> bool {
+		!self.undo.is_empty()
+	}
+
+	// A new or loaded package starts a history of its own
+	pub fn clear(&mut self) {
+		self.undo.clear();
+		self.redo.clear();
+		self.last_edit = None;
+	}
+}
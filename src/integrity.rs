@@ -0,0 +1,282 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Command;
+
+use reqwest::Url;
+
+use crate::error::Result;
+use crate::hashing::{self, Algorithm};
+use crate::worker::{self, Job};
+
+// Files next to the source that may have its checksum, {} is the file name.
+// SHA-256 first, it's the one that's already calculated.
+const CHECKSUM_FILES: &[(Algorithm, &str)] = &[
+	(Algorithm::Sha256, "{}.sha256"),
+	(Algorithm::Sha256, "{}.sha256sum"),
+	(Algorithm::Sha256, "{}.sha256.txt"),
+	(Algorithm::Sha256, "SHA256SUMS"),
+	(Algorithm::Sha256, "SHA256SUMS.txt"),
+	(Algorithm::Sha256, "sha256sums.txt"),
+	(Algorithm::Sha512, "{}.sha512"),
+	(Algorithm::Sha512, "{}.sha512sum"),
+	(Algorithm::Sha512, "SHA512SUMS"),
+	(Algorithm::Blake2b, "{}.b2"),
+	(Algorithm::Blake2b, "B2SUMS")
+];
+const SIGNATURE_FILES: &[&str] = &["{}.asc", "{}.sig", "{}.sign"];
+// Checksum and signature files are small, anything bigger is something else
+const MAX_SIBLING_SIZE: usize = 1024 * 1024;
+
+// Magic numbers of the formats sources come in, with where they start
+const MAGICS: &[(usize, &[u8])] = &[
+	(0, &[0x1f, 0x8b]), // gzip
+	(0, &[0xfd, b'7', b'z', b'X', b'Z', 0x00]), // xz
+	(0, b"BZh"), // bzip2
+	(0, &[0x28, 0xb5, 0x2f, 0xfd]), // zstd
+	(0, b"LZIP"),
+	(0, &[0x5d, 0x00, 0x00]), // lzma
+	(0, b"PK\x03\x04"), // zip and everything based on it (jar, whl...)
+	(0, &[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c]),
+	(257, b"ustar") // uncompressed tar
+];
+
+#[derive(Debug, Clone)]
+pub enum Finding {
+	ChecksumMatches {file: String, algorithm: Algorithm},
+	ChecksumMismatch {file: String, algorithm: Algorithm, expected: String},
+	GoodSignature {file: String, signer: String},
+	BadSignature {file: String, message: String},
+	// There's a signature but it couldn't be checked (no gpgv, no key...)
+	UncheckedSignature {file: String, reason: String},
+	// An error page instead of the source
+	Html,
+	UnknownFormat,
+	NothingToCheck
+}
+
+impl Finding {
+	// Saving with one of these means recording a checksum of something that
+	// isn't (or may not be) the real source
+	pub fn is_fatal(&self) -> bool {
+		matches!(self, Finding::ChecksumMismatch {..} | Finding::BadSignature {..} | Finding::Html)
+	}
+
+	// Worth telling, but it may be fine
+	pub fn is_warning(&self) -> bool {
+		matches!(self, Finding::UncheckedSignature {..} | Finding::UnknownFormat)
+	}
+}
+
+impl fmt::Display for Finding {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Finding::ChecksumMatches {file, algorithm} => write!(f, "The {} checksum matches {}", algorithm.name(), file),
+			Finding::ChecksumMismatch {file, algorithm, expected} => write!(f, "The {} checksum DOESN'T match {}, which says {}", algorithm.name(), file, expected),
+			Finding::GoodSignature {file, signer} => write!(f, "Good signature ({}) from {}", file, signer),
+			Finding::BadSignature {file, message} => write!(f, "BAD signature ({}): {}", file, message),
+			Finding::UncheckedSignature {file, reason} => write!(f, "There's a signature ({}) but it couldn't be checked: {}", file, reason),
+			Finding::Html => write!(f, "The download is a web page, not the source"),
+			Finding::UnknownFormat => write!(f, "The download isn't an archive packmak knows"),
+			Finding::NothingToCheck => write!(f, "Upstream has no checksum or signature next to the source")
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+	pub findings: Vec<Finding>
+}
+
+impl Report {
+	pub fn is_fatal(&self) -> bool {
+		self.findings.iter().any(Finding::is_fatal)
+	}
+
+	// Whether there's something the user should know about
+	pub fn has_concerns(&self) -> bool {
+		self.findings.iter().any(|finding| finding.is_fatal() || finding.is_warning())
+	}
+}
+
+fn is_html(data: &[u8]) -> bool {
+	let start = String::from_utf8_lossy(&data[..data.len().min(512)]).trim_start().to_lowercase();
+	start.starts_with("<!doctype html") || start.starts_with("<html") || (start.starts_with("<?xml") && start.contains("<html"))
+}
+
+fn is_archive(data: &[u8]) -> bool {
+	MAGICS.iter().any(|(offset, magic)| data.get(*offset..offset + magic.len()) == Some(magic))
+}
+
+// A missing file (404) isn't an error, it's just not there
+fn fetch_sibling(url: &Url) -> Option<Vec<u8>> {
+	let resp = reqwest::blocking::get(url.clone()).ok()?.error_for_status().ok()?;
+	let data = resp.bytes().ok()?;
+	if data.len() > MAX_SIBLING_SIZE || is_html(&data) {
+		None
+	}
+	else {
+		Some(data.to_vec())
+	}
+}
+
+fn sibling_url(url: &Url, pattern: &str, file_name: &str) -> Option<Url> {
+	url.join(&pattern.replace("{}", file_name)).ok()
+}
+
+// The checksum for file_name in a .sha256 (just the hash, or with the name)
+// or a SHA256SUMS (a "hash  name" line per file, or BSD's "SHA256 (name) =
+// hash"), hex_len is how long the hashes of the algorithm are. A hash that
+// comes with the name of another file is never taken.
+fn expected_checksum(text: &str, file_name: &str, hex_len: usize) -> Option<String> {
+	let is_hash = |token: &str| token.len() == hex_len && token.chars().all(|c| c.is_ascii_hexdigit());
+	let is_file = |name: &str| name.trim_start_matches('*').trim_start_matches("./") == file_name;
+	let lines = text.lines().map(|line| line.split_whitespace().collect::<Vec<_>>()).filter(|tokens| !tokens.is_empty()).collect::<Vec<_>>();
+
+	let named = lines.iter().find_map(|tokens| match tokens.as_slice() {
+		[hash, name, ..] if is_hash(hash) && is_file(name) => Some(*hash),
+		[_, name, "=", hash] if is_hash(hash) && name.starts_with('(') && name.ends_with(')') && is_file(&name[1..name.len() - 1]) => Some(*hash),
+		_ => None
+	});
+	let alone = || match lines.as_slice() {
+		[tokens] if tokens.len() == 1 && is_hash(tokens[0]) => Some(tokens[0]),
+		_ => None
+	};
+	named.or_else(alone).map(|hash| hash.to_lowercase())
+}
+
+fn check_checksum(url: &Url, file_name: &str, data_path: &Path, sha256: &str, job: &Job) -> Result<Option<Finding>> {
+	for (algorithm, pattern) in CHECKSUM_FILES.iter() {
+		job.check_cancelled()?;
+		let sibling = match sibling_url(url, pattern, file_name) {
+			Some(sibling) => sibling,
+			None => continue
+		};
+		let sibling_name = pattern.replace("{}", file_name);
+
+		if let Some(expected) = fetch_sibling(&sibling).and_then(|data| expected_checksum(&String::from_utf8_lossy(&data), file_name, algorithm.hex_len())) {
+			let actual = match algorithm {
+				Algorithm::Sha256 => sha256.to_lowercase(),
+				_ => {
+					job.step(&format!("Calculating the {} checksum", algorithm.name()));
+					hashing::hash_file(*algorithm, data_path)?
+				}
+			};
+			return Ok(Some(if expected == actual {
+				Finding::ChecksumMatches {file: sibling_name, algorithm: *algorithm}
+			}
+			else {
+				Finding::ChecksumMismatch {file: sibling_name, algorithm: *algorithm, expected}
+			}));
+		}
+	}
+	Ok(None)
+}
+
+// gpgv only trusts the keys in the keyring, keyring in the config or its
+// default (~/.gnupg/trustedkeys.kbx)
+fn run_gpgv(sig_file: &str, signature: &[u8], data_path: &Path, keyring: Option<&Path>) -> Result<Finding> {
+	let mut sig_temp = worker::temp_file(sig_file)?;
+	sig_temp.write_all(signature)?;
+
+	let mut command = Command::new("gpgv");
+	if let Some(keyring) = keyring {
+		command.arg("--keyring").arg(keyring);
+	}
+	let output = command.arg(sig_temp.path()).arg(data_path).output();
+
+	let output = match output {
+		Ok(output) => output,
+		Err(err) => return Ok(Finding::UncheckedSignature {file: sig_file.to_string(), reason: format!("couldn't run gpgv ({})", err)})
+	};
+	let messages = String::from_utf8_lossy(&output.stderr).to_string();
+
+	Ok(if output.status.success() {
+		let signer = messages.lines().find_map(|line| line.split("Good signature from ").nth(1)).map_or("a trusted key".to_string(), |signer| signer.trim().to_string());
+		Finding::GoodSignature {file: sig_file.to_string(), signer}
+	}
+	// Not being able to check isn't the same as the signature being wrong
+	else if messages.contains("No public key") || messages.contains("public key not found") {
+		Finding::UncheckedSignature {file: sig_file.to_string(), reason: "the key isn't in the keyring".to_string()}
+	}
+	else {
+		Finding::BadSignature {file: sig_file.to_string(), message: messages.lines().last().unwrap_or("gpgv failed").to_string()}
+	})
+}
+
+fn check_signature(url: &Url, file_name: &str, data_path: &Path, keyring: Option<&Path>, job: &Job) -> Result<Option<Finding>> {
+	for pattern in SIGNATURE_FILES.iter() {
+		job.check_cancelled()?;
+		let sig_file = pattern.replace("{}", file_name);
+		if let Some(signature) = sibling_url(url, pattern, file_name).and_then(|sibling| fetch_sibling(&sibling)) {
+			return run_gpgv(&sig_file, &signature, data_path, keyring).map(Some);
+		}
+	}
+	Ok(None)
+}
+
+// Enough for is_html and is_archive
+fn read_start(path: &Path) -> Result<Vec<u8>> {
+	let mut start = Vec::new();
+	File::open(path)?.take(512).read_to_end(&mut start)?;
+	Ok(start)
+}
+
+// Checks the source downloaded from url into data_path (which hashes to
+// sha256) against what upstream publishes next to it
+pub fn verify(url: &str, data_path: &Path, sha256: &str, keyring: Option<&Path>, job: &Job) -> Result<Report> {
+	let mut report = Report::default();
+	let start = read_start(data_path)?;
+	if is_html(&start) {
+		report.findings.push(Finding::Html);
+	}
+	else if !is_archive(&start) {
+		report.findings.push(Finding::UnknownFormat);
+	}
+
+	let url = crate::parse_url(url)?;
+	let file_name = match url.path_segments().and_then(|segments| segments.last()).filter(|name| !name.is_empty()) {
+		Some(file_name) => file_name.to_string(),
+		None => return Ok(report)
+	};
+
+	job.step("Looking for upstream checksums and signatures");
+	let checksum = check_checksum(&url, &file_name, data_path, sha256, job)?;
+	let signature = check_signature(&url, &file_name, data_path, keyring, job)?;
+	if checksum.is_none() && signature.is_none() {
+		report.findings.push(Finding::NothingToCheck);
+	}
+	report.findings.extend(checksum);
+	report.findings.extend(signature);
+	Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+	#[test]
+	fn checksum_alone_or_named() {
+		assert_eq!(expected_checksum(HASH, "foo-1.2.tar.gz", 64).as_deref(), Some(HASH));
+		assert_eq!(expected_checksum(&format!("{}  foo-1.2.tar.gz\n", HASH), "foo-1.2.tar.gz", 64).as_deref(), Some(HASH));
+		assert_eq!(expected_checksum(&format!("{} *./foo-1.2.tar.gz", HASH), "foo-1.2.tar.gz", 64).as_deref(), Some(HASH));
+		assert_eq!(expected_checksum(&format!("SHA256 (foo-1.2.tar.gz) = {}", HASH), "foo-1.2.tar.gz", 64).as_deref(), Some(HASH));
+	}
+
+	#[test]
+	fn checksum_of_another_file() {
+		// A single line that names a different file isn't about this one
+		assert_eq!(expected_checksum(&format!("{}  foo-1.2.tar.xz", HASH), "foo-1.2.tar.gz", 64), None);
+		assert_eq!(expected_checksum(&format!("SHA256 (foo-1.2.tar.xz) = {}", HASH), "foo-1.2.tar.gz", 64), None);
+		let sums = format!("{}  foo-1.2.tar.xz\n{}  foo-1.2.tar.gz", "0".repeat(64), HASH);
+		assert_eq!(expected_checksum(&sums, "foo-1.2.tar.gz", 64).as_deref(), Some(HASH));
+	}
+
+	#[test]
+	fn checksum_of_other_length() {
+		assert_eq!(expected_checksum(HASH, "foo-1.2.tar.gz", 128), None);
+	}
+}
@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::error::Result;
+use crate::components::{self, Component};
+use crate::mirrors;
+use crate::vars::{PACKAGE_KEYS, SPDX_EXCEPTIONS, SPDX_LICENSES};
+use crate::version::Version;
+use crate::{PkgData, YamlPkg};
+
+// Longer summaries get cut in the Software Center
+const MAX_SUMMARY_LEN: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+	Name,
+	Version,
+	Source,
+	License,
+	Component,
+	Summary,
+	Description,
+	BuildDeps,
+	// Anything the editor doesn't show
+	Other
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Severity {
+	Warning,
+	// Won't be saved like this
+	Error
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub rule: &'static str,
+	pub field: Field,
+	pub severity: Severity,
+	pub message: String
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let severity = match self.severity {
+			Severity::Warning => "warning",
+			Severity::Error => "error"
+		};
+		write!(f, "{} [{}]: {}", severity, self.rule, self.message)
+	}
+}
+
+struct Lints(Vec<Diagnostic>);
+
+impl Lints {
+	fn error<S: Into<String>>(&mut self, rule: &'static str, field: Field, message: S) {
+		self.0.push(Diagnostic {rule, field, severity: Severity::Error, message: message.into()});
+	}
+
+	fn warning<S: Into<String>>(&mut self, rule: &'static str, field: Field, message: S) {
+		self.0.push(Diagnostic {rule, field, severity: Severity::Warning, message: message.into()});
+	}
+}
+
+pub fn has_errors(diags: &[Diagnostic]) -> bool {
+	diags.iter().any(|diag| diag.severity == Severity::Error)
+}
+
+fn is_spdx_id(id: &str) -> bool {
+	SPDX_LICENSES.contains(&id) || id.starts_with("LicenseRef-")
+}
+
+enum Token<'a> {
+	Open,
+	Close,
+	And,
+	Or,
+	With,
+	Id(&'a str)
+}
+
+// Parentheses don't need spaces around them
+fn tokens(license: &str) -> Vec<Token<'_>> {
+	let mut tokens = Vec::new();
+	for word in license.split_whitespace() {
+		let mut rest = word;
+		while !rest.is_empty() {
+			let end = rest.find(|c| c == '(' || c == ')').map_or(rest.len(), |pos| pos.max(1));
+			tokens.push(match &rest[..end] {
+				"(" => Token::Open,
+				")" => Token::Close,
+				"AND" => Token::And,
+				"OR" => Token::Or,
+				"WITH" => Token::With,
+				id => Token::Id(id)
+			});
+			rest = &rest[end..];
+		}
+	}
+	tokens
+}
+
+// The licenses and exceptions (true) of an SPDX expression, or what's wrong
+// with how it's written
+fn parse_expression(license: &str) -> std::result::Result<Vec<(&str, bool)>, String> {
+	let mut ids = Vec::new();
+	let mut depth = 0;
+	// Whether a license (or an opening parenthesis) has to come next
+	let mut want_license = true;
+	let mut want_exception = false;
+
+	for token in tokens(license) {
+		match token {
+			Token::Id(id) if want_exception => {
+				ids.push((id, true));
+				want_exception = false;
+			}
+			Token::Id(id) if want_license => {
+				ids.push((id, false));
+				want_license = false;
+			}
+			Token::Id(id) => return Err(format!("\"{}\" needs an AND or OR before it", id)),
+			_ if want_exception => return Err("WITH has to be followed by an exception".to_string()),
+			Token::Open if want_license => depth += 1,
+			Token::Close if !want_license && depth > 0 => depth -= 1,
+			Token::And | Token::Or if !want_license => want_license = true,
+			Token::With if !want_license => want_exception = true,
+			Token::Open | Token::Close => return Err("The parentheses don't match".to_string()),
+			Token::And | Token::Or | Token::With => return Err("AND, OR and WITH go between licenses".to_string())
+		}
+	}
+
+	if want_exception {
+		Err("WITH has to be followed by an exception".to_string())
+	}
+	else if want_license {
+		Err("The expression ends without a license".to_string())
+	}
+	else if depth > 0 {
+		Err("The parentheses don't match".to_string())
+	}
+	else {
+		Ok(ids)
+	}
+}
+
+// Licenses can be expressions like "MIT OR Apache-2.0" or "GPL-2.0-or-later WITH Classpath-exception-2.0".
+// Only a malformed expression is an error, the lists packmak has aren't the
+// whole SPDX list so an id it doesn't know is just a warning.
+fn check_license(license: &str, lints: &mut Lints) {
+	if license.is_empty() || license == "Unknown" {
+		lints.error("spdx-license", Field::License, "The license is unknown");
+		return;
+	}
+
+	let ids = match parse_expression(license) {
+		Ok(ids) => ids,
+		Err(err) => return lints.error("spdx-license", Field::License, err)
+	};
+	for (id, exception) in ids {
+		if exception {
+			if !SPDX_EXCEPTIONS.contains(&id) {
+				lints.warning("spdx-license", Field::License, format!("\"{}\" isn't an SPDX license exception packmak knows", id));
+			}
+		}
+		// Older SPDX lists had these ("+" meant "or later"), they are ambiguous
+		else if SPDX_LICENSES.contains(&(id.trim_end_matches('+').to_string() + "-only").as_str()) {
+			let base = id.trim_end_matches('+');
+			lints.warning("spdx-license", Field::License, format!("\"{}\" is deprecated, use {}-only or {}-or-later", id, base, base));
+		}
+		else if !is_spdx_id(id) {
+			lints.warning("spdx-license", Field::License, format!("\"{}\" isn't an SPDX license identifier packmak knows", id));
+		}
+	}
+}
+
+// The rules that only need what's in the editor
+pub fn lint_pkg(pkg: &PkgData, components: &[Component]) -> Vec<Diagnostic> {
+	let mut lints = Lints(Vec::new());
+
+	let required = [
+		(Field::Name, &pkg.name, "name"),
+		(Field::Version, &pkg.version, "version"),
+		(Field::Source, &pkg.source, "source"),
+		(Field::Description, &pkg.description, "description")
+	];
+	for (field, value, key) in required.iter() {
+		if value.trim().is_empty() {
+			lints.error("required-field", *field, format!("The {} is missing", key));
+		}
+	}
+
+	if let Some(reason) = mirrors::unstable(&pkg.source) {
+		lints.warning("unstable-source", Field::Source, reason);
+	}
+
+	if pkg.name.chars().any(|c| c.is_uppercase()) {
+		lints.error("lowercase-name", Field::Name, format!("Package names are lowercase, \"{}\" isn't", pkg.name));
+	}
+
+	if pkg.version.contains('-') {
+		lints.error("version-dashes", Field::Version, format!("Versions can't have dashes, use \"{}\"", pkg.version.replace('-', ".")));
+	}
+	else if let (false, Err(err)) = (pkg.version.is_empty(), Version::parse(&pkg.version)) {
+		lints.error("version-dashes", Field::Version, err.to_string());
+	}
+
+	let summary = pkg.summary.trim();
+	if summary.is_empty() {
+		lints.error("summary-length", Field::Summary, "The summary is missing");
+	}
+	else if summary.chars().count() > MAX_SUMMARY_LEN {
+		lints.warning("summary-length", Field::Summary, format!("The summary is {} characters long, keep it under {}", summary.chars().count(), MAX_SUMMARY_LEN));
+	}
+	if summary.ends_with('.') {
+		lints.warning("summary-period", Field::Summary, "The summary shouldn't end with a period");
+	}
+
+	check_license(&pkg.license, &mut lints);
+
+	if !components::exists(components, &pkg.component) {
+		lints.error("component-exists", Field::Component, format!("There's no component called \"{}\"", pkg.component));
+	}
+
+	let mut seen = HashSet::new();
+	for dep in pkg.build_deps.iter() {
+		if !seen.insert(dep) {
+			lints.warning("duplicate-builddeps", Field::BuildDeps, format!("\"{}\" is more than once in builddeps", dep));
+		}
+	}
+
+	lints.0
+}
+
+// Everything, for a package.yml as it is on disk
+pub fn lint_file(text: &str, components: &[Component]) -> Result<Vec<Diagnostic>> {
+	let raw: serde_yaml::Value = serde_yaml::from_str(text)?;
+	let yaml: YamlPkg = serde_yaml::from_str(text)?;
+	let mut lints = Lints(Vec::new());
+
+	if let Some(mapping) = raw.as_mapping() {
+		for key in mapping.iter().filter_map(|(key, _)| key.as_str()) {
+			if !PACKAGE_KEYS.contains(&key) {
+				lints.warning("deprecated-key", Field::Other, format!("ypkg doesn't read \"{}\" (anymore?), it can be removed", key));
+			}
+		}
+	}
+
+	// Git sources point to a commit instead
+	for (url, sha) in yaml.source.iter().flat_map(|source| source.iter()) {
+		if !url.starts_with("git|") && (sha.len() != 64 || !sha.chars().all(|c| c.is_ascii_hexdigit())) {
+			lints.error("source-checksum", Field::Source, format!("The checksum of {} isn't a SHA-256 (64 hex characters)", url));
+		}
+	}
+
+	let mut diags = lint_pkg(&yaml.into(), components);
+	diags.extend(lints.0);
+	Ok(diags)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn license_lints(license: &str) -> Vec<(Severity, String)> {
+		let mut lints = Lints(Vec::new());
+		check_license(license, &mut lints);
+		lints.0.into_iter().map(|diag| (diag.severity, diag.message)).collect()
+	}
+
+	#[test]
+	fn parses_expressions() {
+		assert_eq!(parse_expression("MIT"), Ok(vec![("MIT", false)]));
+		assert_eq!(parse_expression("MIT OR Apache-2.0"), Ok(vec![("MIT", false), ("Apache-2.0", false)]));
+		assert_eq!(parse_expression("GPL-2.0-or-later WITH Classpath-exception-2.0"), Ok(vec![("GPL-2.0-or-later", false), ("Classpath-exception-2.0", true)]));
+		assert_eq!(parse_expression("(MIT OR Apache-2.0) AND BSD-3-Clause"), Ok(vec![("MIT", false), ("Apache-2.0", false), ("BSD-3-Clause", false)]));
+		// Parentheses don't need spaces
+		assert_eq!(parse_expression("(MIT OR (Zlib AND ISC))"), Ok(vec![("MIT", false), ("Zlib", false), ("ISC", false)]));
+	}
+
+	#[test]
+	fn rejects_malformed_expressions() {
+		for license in ["MIT Apache-2.0", "MIT OR", "AND MIT", "MIT WITH", "MIT WITH OR Zlib", "(MIT", "MIT)", "()", "MIT ()"].iter() {
+			assert!(parse_expression(license).is_err(), "{}", license);
+		}
+	}
+
+	#[test]
+	fn only_malformed_licenses_are_errors() {
+		assert!(license_lints("MIT OR Apache-2.0").is_empty());
+		assert!(license_lints("LicenseRef-Proprietary").is_empty());
+		assert_eq!(license_lints("MIT OR").len(), 1);
+		assert_eq!(license_lints("MIT OR")[0].0, Severity::Error);
+		assert_eq!(license_lints("Unknown")[0].0, Severity::Error);
+		assert_eq!(license_lints("Not-A-License")[0].0, Severity::Warning);
+		assert_eq!(license_lints("MIT WITH Not-An-Exception")[0].0, Severity::Warning);
+		let deprecated = license_lints("GPL-2.0+");
+		assert_eq!(deprecated[0].0, Severity::Warning);
+		assert!(deprecated[0].1.contains("GPL-2.0-or-later"));
+	}
+}
@@ -1,16 +1,36 @@
 mod vars;
 mod guess;
 mod build_deps_mngr;
+mod version;
+mod metadata;
+mod config;
+mod description;
+mod error;
+mod worker;
+mod history;
+mod diff;
+mod release;
+mod lint;
+mod components;
+mod patches;
+mod subpackages;
+mod build;
+mod build_log;
+mod workspace;
+mod git;
+mod changelog;
+mod advisory;
+mod integrity;
+mod hashing;
+mod mirrors;
 
-use std::rc::Rc;
 use gtk::{Inhibit, ComboBoxExt, ComboBoxTextExt, ComboBoxText, TreeModelExt, FileChooserExt, TextBufferExt};
 use gtk::prelude::*;
 use gtk::DialogFlags;
 use relm_derive::{Msg, widget};
-use relm::{Component, Widget, init, connect, Relm};
+use relm::{Channel, Component, Widget, init, connect, Relm};
 use serde::{Serialize, Deserialize};
 use std::collections::BTreeMap;
-use sha::sha256::ops::digest;
 use reqwest::Url;
 use regex::Regex;
 use crate::vars::*;
@@ -18,7 +38,25 @@ use std::path::{PathBuf, Path};
 use std::process::Command;
 use std::string::ToString;
 use std::cell::RefCell;
+use std::io::Write;
 use crate::build_deps_mngr::show_build_deps;
+use crate::version::{Version, VersionError};
+use crate::metadata::{MetadataError, UpstreamMeta};
+use crate::guess::SourceGuess;
+use crate::error::{Error, Result};
+use crate::worker::{CancelHandle, Job};
+use crate::config::Config;
+use crate::history::History;
+use crate::release::ReleaseInfo;
+use crate::lint::{Diagnostic, Field, Severity};
+use crate::components::{Component as PkgComponent, Signals as ComponentSignals};
+use crate::subpackages::{Subpackage, Subpackages, SubpkgList};
+use crate::patches::PendingFiles;
+use crate::build::BuildReport;
+use crate::workspace::PackageEntry;
+use crate::changelog::Changelog;
+use crate::integrity::Report as IntegrityReport;
+use std::sync::Arc;
 
 use self::HeaderMsg::*;
 use self::WinMsg::*;
@@ -29,17 +67,26 @@ use self::WinMsg::*;
 pub enum HeaderMsg {
     BtnNew,
     Load,
+    BtnOpenRepo,
     BtnFromUrl,
     NewSubtitle(String),
     FileModified,
     FileSaved,
+    // Work is going on in the background, most actions have to wait
+    Busy(bool),
     BtnBuildDeps,
-    BtnSave
+    BtnPatches,
+    BtnBuild,
+    BtnCommit,
+    BtnSecurity,
+    BtnSave,
+    BtnSaveAs
 }
 
 pub struct HeaderModel {
     subtitle: String,
     is_saved: bool,
+    busy: bool,
     pkg_name: String
 }
 #[widget]
@@ -48,6 +95,7 @@ impl Widget for Header {
         HeaderModel {
             subtitle: "Untitled *".to_string(),
             is_saved: false,
+            busy: false,
             pkg_name: "Untitled".to_string()
         }
     }
@@ -87,6 +135,9 @@ impl Widget for Header {
                     self.model.subtitle = make_sub(&self.model);
                 }
             }
+            Busy(busy) => {
+                self.model.busy = busy;
+            }
             _ => {}
         }
     }
@@ -100,29 +151,69 @@ impl Widget for Header {
 
             gtk::Button {
                 clicked => BtnNew,
+                sensitive: !self.model.busy,
                 //label: "New",
                 image: Some(&gtk::Image::new_from_icon_name(Some("document-new-symbolic"), gtk::IconSize::Button))
             },
             #[name="load_button"]
             gtk::Button {
                 clicked => Load,
+                sensitive: !self.model.busy,
                 //label: "Load",
                 image: Some(&gtk::Image::new_from_icon_name(Some("document-open-symbolic"), gtk::IconSize::Button))
             },
+            gtk::Button {
+                clicked => BtnOpenRepo,
+                sensitive: !self.model.busy,
+                tooltip_text: Some("Open a packaging repository"),
+                image: Some(&gtk::Image::new_from_icon_name(Some("folder-open-symbolic"), gtk::IconSize::Button))
+            },
 
             gtk::Button {
                 clicked => BtnFromUrl,
+                sensitive: !self.model.busy,
                 label: "From URL"
             },
             gtk::Button {
                 clicked => BtnBuildDeps,
+                sensitive: !self.model.busy,
                 label: "Build deps"
             },
+            gtk::Button {
+                clicked => BtnPatches,
+                sensitive: !self.model.busy,
+                label: "Patches"
+            },
+            gtk::Button {
+                clicked => BtnBuild,
+                sensitive: !self.model.busy,
+                label: "Build",
+                tooltip_text: Some("Build the saved package.yml with solbuild or ypkg-build")
+            },
+            gtk::Button {
+                clicked => BtnCommit,
+                sensitive: !self.model.busy,
+                label: "Commit",
+                tooltip_text: Some("Commit the package folder to git")
+            },
+            gtk::Button {
+                clicked => BtnSecurity,
+                sensitive: !self.model.busy,
+                label: "Security",
+                tooltip_text: Some("CVEs fixed by this update")
+            },
             gtk::Button {
                 //label: "Save",
                 image: Some(&gtk::Image::new_from_icon_name(Some("document-save-symbolic"), gtk::IconSize::Button)),
-                sensitive: !self.model.is_saved,
+                sensitive: !self.model.is_saved && !self.model.busy,
                 clicked => BtnSave
+            },
+            gtk::Button {
+                //label: "Save As",
+                image: Some(&gtk::Image::new_from_icon_name(Some("document-save-as-symbolic"), gtk::IconSize::Button)),
+                tooltip_text: Some("Save As"),
+                sensitive: !self.model.busy,
+                clicked => BtnSaveAs
             }
         }
     }
@@ -131,7 +222,56 @@ impl Widget for Header {
 pub struct Model {
     header: Component<Header>,
     pkg_data: PkgData,
-    can_start: bool
+    can_start: bool,
+    // What's wrong with pkg_data right now
+    diagnostics: Vec<Diagnostic>,
+    components: Vec<PkgComponent>,
+    // Components that fit the package, best first, the chosen one isn't there
+    suggested_components: Vec<String>,
+    // Background work sends its messages through here
+    _channel: Channel<WinMsg>,
+    sender: relm::Sender<WinMsg>,
+    job: Option<CancelHandle>,
+    busy: bool,
+    // Read once at startup, see init_view
+    config: Config,
+    // Where the save in progress goes when it was chosen with "Save As"
+    save_as: Option<PathBuf>,
+    history: History<PkgData>,
+    // Whether what's in the editor is what's on disk
+    saved: bool,
+    // Subpackage shown in the subpackage editor, "" is the main one
+    subpkg: String,
+    // Open packages, the one in the editor is tabs[tab]
+    tabs: Vec<Tab>,
+    tab: usize,
+    next_tab_id: u64,
+    // Packages of the open repository and which of them the sidebar shows
+    workspace: Vec<PackageEntry>,
+    workspace_root: Option<PathBuf>,
+    shown: Vec<usize>
+}
+
+// What a package that isn't in the editor right now keeps
+struct TabState {
+    pkg_data: PkgData,
+    history: History<PkgData>,
+    saved: bool,
+    subpkg: String
+}
+
+impl TabState {
+    // saved is false for a new package, true for one just loaded
+    fn new(pkg_data: PkgData, saved: bool) -> Self {
+        TabState {pkg_data, history: History::new(), saved, subpkg: String::new()}
+    }
+}
+
+struct Tab {
+    id: u64,
+    label: gtk::Label,
+    // None for the one in the editor, its state is in Model
+    stored: Option<TabState>
 }
 
 #[derive(Debug, Clone)]
@@ -144,22 +284,88 @@ pub struct PkgData {
     component: String,
     summary: String,
     description: String,
+    homepage: Option<String>,
+    // Descriptions found upstream, offered in the description editor
+    upstream_descriptions: Vec<(String, String)>,
+    // What From URL found that hints at the component
+    component_signals: ComponentSignals,
     build_sys: String,
     org_yaml: Option<YamlPkg>,
     file_path: Option<PathBuf>,
-    tarball_data: RefCell<Option<Rc<Vec<u8>>>>,
-    build_deps: Vec<String>
+    // The file as it was when loaded or last saved, if it changed since then it isn't overwritten
+    file_text: Option<String>,
+    // Releases this package already had, only for loaded ones
+    release_info: Option<ReleaseInfo>,
+    // The release was set by hand, edits leave it as it is from then on
+    release_edited: bool,
+    tarball_data: RefCell<Option<Arc<Vec<u8>>>>,
+    // The last check of a file source, so saving again doesn't download it
+    source_check: Option<SourceCheck>,
+    build_deps: Vec<String>,
+    // Applied in setup, in order, relative to files/
+    patches: Vec<String>,
+    // Patches added to or removed from files/ since the last save
+    patch_files: PendingFiles,
+    // patterns, rundeps and replaces of every subpackage, "" is the main one
+    subpackages: Subpackages,
+    // What changed upstream since the version that was loaded
+    changelog: Option<Changelog>,
+    // Security issues this update fixes, found in the changelog or added by hand
+    cves: Vec<String>
+}
+
+fn cant_start_dialog(parent: &gtk::Window, diags: &[Diagnostic]) {
+    let errors = diags.iter().filter(|diag| diag.severity == Severity::Error).map(|diag| format!("• {}", diag.message)).collect::<Vec<_>>();
+    let msg = format!("Can't save because of these problems:\n\n{}", errors.join("\n"));
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Error, gtk::ButtonsType::Ok, &msg);
+    dialog.show_all();
+    dialog.run();
+    dialog.destroy();
+}
+
+fn error_dialog(parent: &gtk::Window, title: &str, err: &Error) {
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Error, gtk::ButtonsType::Ok, title);
+    dialog.set_property_secondary_text(Some(&err.to_string()));
+    dialog.show_all();
+    dialog.run();
+    dialog.destroy();
 }
 
-fn cant_start_dialog(parent: &gtk::Window) {
-    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Error, gtk::ButtonsType::Ok, "Can't save because not everything has been filled up below");
+fn warning_dialog(parent: &gtk::Window, msg: &str) {
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Warning, gtk::ButtonsType::Ok, msg);
     dialog.show_all();
     dialog.run();
     dialog.destroy();
 }
 
-fn ask_for_url(parent: &gtk::Window) -> Option<String> {
-    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::OkCancel, "Please enter the desired URL to analyze");
+fn confirm_dialog(parent: &gtk::Window, question: &str) -> bool {
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::OkCancel, question);
+    let response = dialog.run();
+    dialog.destroy();
+    response == gtk::ResponseType::Ok
+}
+
+// True if the user still wants to go ahead
+fn downgrade_dialog(parent: &gtk::Window, old: &Version, new: &Version) -> bool {
+    let msg = format!("The version would go down from {} to {}, do you want to continue?", old, new);
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Warning, gtk::ButtonsType::OkCancel, &msg);
+    dialog.show_all();
+    let response = dialog.run();
+    dialog.destroy();
+
+    response == gtk::ResponseType::Ok
+}
+
+// Some((old, new)) if going from old to new would decrease the version
+fn version_downgrade(old: &str, new: &str) -> Option<(Version, Version)> {
+    match (Version::parse(old), Version::parse(new)) {
+        (Ok(old), Ok(new)) if new < old => Some((old, new)),
+        _ => None
+    }
+}
+
+fn ask_for_text(parent: &gtk::Window, question: &str) -> Option<String> {
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::OkCancel, question);
     let url_entry = gtk::Entry::new();
     dialog.get_content_area().pack_end(&url_entry, false, false, 0);
     dialog.show_all();
@@ -177,6 +383,220 @@ fn ask_for_url(parent: &gtk::Window) -> Option<String> {
 
 }
 
+// A diff with added and removed lines in color
+fn diff_view(diff: &str) -> gtk::ScrolledWindow {
+    let buffer = gtk::TextBuffer::new::<gtk::TextTagTable>(None);
+    let tags = buffer.get_tag_table().unwrap();
+    let added = gtk::TextTag::new(Some("added"));
+    added.set_property_foreground(Some("#26a269"));
+    let removed = gtk::TextTag::new(Some("removed"));
+    removed.set_property_foreground(Some("#c01c28"));
+    tags.add(&added);
+    tags.add(&removed);
+
+    for line in diff.lines() {
+        let start = buffer.get_char_count();
+        buffer.insert(&mut buffer.get_end_iter(), &(line.to_string() + "\n"));
+        let tag = {
+            if line.starts_with('+') && !line.starts_with("+++") {
+                Some(&added)
+            }
+            else if line.starts_with('-') && !line.starts_with("---") {
+                Some(&removed)
+            }
+            else {
+                None
+            }
+        };
+        if let Some(tag) = tag {
+            buffer.apply_tag(tag, &buffer.get_iter_at_offset(start), &buffer.get_end_iter());
+        }
+    }
+
+    let view = gtk::TextView::new_with_buffer(&buffer);
+    view.set_editable(false);
+    view.set_property_monospace(true);
+    let scroll = gtk::ScrolledWindow::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+    scroll.set_vexpand(true);
+    scroll.add(&view);
+    scroll
+}
+
+// Shows what saving changes, returns whether to go on with it
+fn review_dialog(parent: &gtk::Window, diff: &str) -> bool {
+    let dialog = gtk::Dialog::new_with_buttons::<gtk::Window>(Some("Review changes"), Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, &[("Cancel", gtk::ResponseType::Cancel), ("Save", gtk::ResponseType::Ok)]);
+    dialog.set_default_size(700, 500);
+    dialog.set_default_response(gtk::ResponseType::Ok);
+    dialog.get_content_area().pack_start(&diff_view(diff), true, true, 0);
+    dialog.show_all();
+
+    let response = dialog.run();
+    dialog.destroy();
+
+    response == gtk::ResponseType::Ok
+}
+
+// Tells what's wrong with the source, returns whether to save anyway. When
+// it's the wrong file the safe choice is the default.
+fn integrity_dialog(parent: &gtk::Window, report: &IntegrityReport) -> bool {
+    let (kind, title, go_on) = if report.is_fatal() {
+        (gtk::MessageType::Error, "The source doesn't match what upstream published", "Save anyway")
+    }
+    else {
+        (gtk::MessageType::Warning, "The source couldn't be fully verified", "Save")
+    };
+
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, kind, gtk::ButtonsType::None, title);
+    dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+    dialog.add_button(go_on, gtk::ResponseType::Ok);
+    dialog.set_default_response(if report.is_fatal() {gtk::ResponseType::Cancel} else {gtk::ResponseType::Ok});
+    let findings = report.findings.iter().map(|finding| format!("• {}", finding)).collect::<Vec<_>>();
+    dialog.set_property_secondary_text(Some(&findings.join("\n")));
+    dialog.show_all();
+
+    let response = dialog.run();
+    dialog.destroy();
+    response == gtk::ResponseType::Ok
+}
+
+// Shows what would be committed, gives back the message and whether to write
+// an advisory if the user goes on. It's only offered with some CVE to tell about.
+fn commit_dialog(parent: &gtk::Window, diff: &str, message: &str, has_cves: bool) -> Option<(String, bool)> {
+    let dialog = gtk::Dialog::new_with_buttons::<gtk::Window>(Some("Commit"), Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, &[("Cancel", gtk::ResponseType::Cancel), ("Commit", gtk::ResponseType::Ok)]);
+    dialog.set_default_size(700, 600);
+
+    let txt_message = gtk::TextView::new();
+    txt_message.set_property_monospace(true);
+    txt_message.set_property_height_request(120);
+    let buffer = txt_message.get_buffer().unwrap();
+    buffer.set_text(message);
+
+    dialog.get_content_area().pack_start(&diff_view(diff), true, true, 0);
+    dialog.get_content_area().pack_start(&gtk::Label::new(Some("Commit message")), false, false, 6);
+    dialog.get_content_area().pack_start(&txt_message, false, false, 0);
+    let chk_advisory = gtk::CheckButton::new_with_label("Write a security advisory");
+    chk_advisory.set_active(has_cves);
+    dialog.get_content_area().pack_start(&chk_advisory, false, false, 6);
+    dialog.show_all();
+    chk_advisory.set_visible(has_cves);
+
+    let response = dialog.run();
+    let message = buffer.get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false).unwrap().to_string();
+    let advisory = has_cves && chk_advisory.get_active();
+    dialog.destroy();
+
+    if response == gtk::ResponseType::Ok && !message.trim().is_empty() {Some((message, advisory))} else {None}
+}
+
+// Edits the CVEs an update fixes, one per line. None if cancelled.
+fn cves_dialog(parent: &gtk::Window, cves: &[String]) -> Option<Vec<String>> {
+    let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::OkCancel, "Security issues this update fixes");
+    dialog.set_property_secondary_text(Some("One CVE id per line, like CVE-2020-1234"));
+    let txt_cves = gtk::TextView::new();
+    txt_cves.set_property_monospace(true);
+    txt_cves.set_property_height_request(150);
+    let buffer = txt_cves.get_buffer().unwrap();
+    buffer.set_text(&cves.join("\n"));
+    dialog.get_content_area().pack_end(&txt_cves, true, true, 0);
+    dialog.show_all();
+
+    let response = dialog.run();
+    let text = buffer.get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false).unwrap().to_string();
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Ok {
+        return None;
+    }
+
+    let invalid = text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && changelog::cve_ids(line).is_empty()).collect::<Vec<_>>();
+    if !invalid.is_empty() {
+        warning_dialog(parent, &format!("These aren't CVE ids and were left out: {}", invalid.join(", ")));
+    }
+    Some(changelog::cve_ids(&text))
+}
+
+// Lets the user pick which of the values found upstream are used, returns
+// false if nothing should be
+fn merge_dialog(parent: &gtk::Window, choices: &mut [MergeChoice]) -> bool {
+    // Long values (descriptions) are cut so that the dialog stays usable
+    fn value_label(value: &str) -> gtk::Label {
+        let shown = if value.chars().count() > MERGE_MAX_CHARS {
+            value.chars().take(MERGE_MAX_CHARS).collect::<String>() + "…"
+        }
+        else {
+            value.to_string()
+        };
+        let label = gtk::Label::new(Some(&shown));
+        label.set_line_wrap(true);
+        label.set_max_width_chars(40);
+        label.set_halign(gtk::Align::Start);
+        label.set_selectable(true);
+        label.set_tooltip_text(Some(value));
+        label
+    }
+
+    let dialog = gtk::Dialog::new_with_buttons::<gtk::Window>(Some("Use data found upstream"), Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, &[("Cancel", gtk::ResponseType::Cancel), ("Apply", gtk::ResponseType::Ok)]);
+    dialog.set_default_response(gtk::ResponseType::Ok);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_border_width(12);
+    for (col, title) in ["", "Current", "Found upstream"].iter().enumerate() {
+        let label = gtk::Label::new(None);
+        label.set_markup(&format!("<b>{}</b>", title));
+        label.set_halign(gtk::Align::Start);
+        grid.attach(&label, col as i32, 0, 1, 1);
+    }
+
+    let mut checks = Vec::new();
+    for (row, choice) in choices.iter().enumerate() {
+        let row = row as i32 + 1;
+        let check = gtk::CheckButton::new_with_label(choice.field.label());
+        check.set_active(choice.accept);
+        check.set_valign(gtk::Align::Start);
+        grid.attach(&check, 0, row, 1, 1);
+        grid.attach(&value_label(&choice.current), 1, row, 1, 1);
+        grid.attach(&value_label(&choice.detected), 2, row, 1, 1);
+        checks.push(check);
+    }
+
+    dialog.get_content_area().pack_start(&grid, true, true, 0);
+    dialog.show_all();
+    let response = dialog.run();
+    for (choice, check) in choices.iter_mut().zip(checks.iter()) {
+        choice.accept = check.get_active();
+    }
+    dialog.destroy();
+
+    response == gtk::ResponseType::Ok
+}
+
+fn ask_for_save_path(parent: &gtk::Window, pkg_data: &PkgData, config: &Config) -> Option<PathBuf> {
+    let chooser = gtk::FileChooserDialog::with_buttons::<gtk::Window>(Some("Save package.yml"), Some(parent), gtk::FileChooserAction::Save, &[("Save",gtk::ResponseType::Ok)]);
+    chooser.set_do_overwrite_confirmation(true);
+    let folder = match &pkg_data.file_path {
+        Some(file_path) => file_path.parent().map(|dir| dir.to_path_buf()),
+        None => Some(config.packages_root())
+    };
+    if let Some(folder) = folder {
+        chooser.set_current_folder(folder);
+    }
+    chooser.set_current_name("package.yml");
+    chooser.show_all();
+    let response = chooser.run();
+    let opt_file = chooser.get_filename();
+    chooser.destroy();
+
+    if response == gtk::ResponseType::Ok {
+        opt_file
+    }
+    else {
+        None
+    }
+
+}
+
 fn ask_for_file(parent: &gtk::Window) -> Option<std::path::PathBuf> {
     let chooser = gtk::FileChooserDialog::with_buttons::<gtk::Window>(Some("Select package.yml"), Some(parent), gtk::FileChooserAction::Open, &[("Open",gtk::ResponseType::Ok)]);
     chooser.show_all();
@@ -204,183 +624,195 @@ impl PkgData {
             component: "database".to_string(),
             summary: "".to_string(),
             description: "".to_string(),
+            homepage: None,
+            upstream_descriptions: Vec::new(),
+            component_signals: ComponentSignals::default(),
             build_sys: "None".to_string(),
             org_yaml: None,
             file_path: None,
+            file_text: None,
+            release_info: None,
+            release_edited: false,
             tarball_data: RefCell::new(None),
-            build_deps: Vec::new()
+            source_check: None,
+            build_deps: Vec::new(),
+            patches: Vec::new(),
+            patch_files: PendingFiles::default(),
+            subpackages: Subpackages::new(),
+            changelog: None,
+            cves: Vec::new()
+        }
+    }
+
+    // Where package.yml is or will be saved
+    fn pkg_dir(&self, config: &Config) -> Option<PathBuf> {
+        match &self.file_path {
+            Some(file_path) => file_path.parent().map(|dir| dir.to_path_buf()),
+            None if !self.name.is_empty() => Some(config.packages_root().join(&self.name)),
+            None => None
         }
     }
 
     fn set_source(&mut self, new_source: String) {
         self.source = new_source.to_string();
         self.tarball_data = RefCell::new(None);
+        self.source_check = None;
     }
 
-    fn is_filled(&self) -> bool {
-        !self.name.is_empty() && !self.version.is_empty() && !self.license.is_empty() && !self.component.is_empty() && !self.summary.is_empty() && !self.description.is_empty() && !self.build_sys.is_empty() && !self.source.is_empty()
-    }
-
-    fn join_url_data(&mut self, url_data: PkgDataUrl) {
-        if self.name.is_empty() {
-            self.name = url_data.name;
-        }
-
-        if url_data.summary.is_some() && self.summary.is_empty() {
-            self.summary = url_data.summary.clone().unwrap();
-        }
-
-        // For now just clone the summary
-        if url_data.summary.is_some() && self.description.is_empty() {
-            self.description = url_data.summary.unwrap();
-        }
-
-        if url_data.license.is_some() && self.license == "Unknown" {
-            self.license = url_data.license.unwrap().to_string();
-        }
-
-        if url_data.build_sys.is_some() && self.build_sys == "None" {
-            self.build_sys = url_data.build_sys.unwrap().to_string();
+    // The first change to a package that was already released takes it one
+    // release up, later ones don't, nor any once the release was set by hand
+    fn bump_release_once(&mut self) {
+        if let Some(info) = self.release_info.filter(|_| !self.release_edited) {
+            if let Some(next) = info.base().checked_add(1).filter(|next| self.release < *next) {
+                self.release = next;
+            }
         }
+    }
 
-        self.set_source(url_data.source);
-        self.version = url_data.version;
-        self.tarball_data = RefCell::new({
-            if let Some(buffer) = url_data.tarball_data {
-                Some(Rc::new(buffer))
-            }
-            else {
-                None
+    // What From URL found that isn't already there, accept is preselected so
+    // that nothing curated by hand is replaced unless the user says so
+    fn merge_choices(&self, url_data: &PkgDataUrl) -> Vec<MergeChoice> {
+        let version = {
+            // A snapshot of a repo we already have a release of goes on top of that release
+            match (Version::parse(&url_data.version), Version::parse(&self.version)) {
+                (Ok(new), Ok(current)) if new.is_snapshot() => Version::snapshot(Some(&current), chrono::Utc::now()).to_string(),
+                _ => url_data.version.clone()
             }
-        });
+        };
+        let is_downgrade = version_downgrade(&self.version, &version).is_some();
+        // If upstream has no description just clone the summary
+        let description = url_data.description.clone().or_else(|| url_data.summary.clone());
+
+        let choices = vec![
+            (MergeField::Name, self.name.clone(), Some(url_data.name.clone()), self.name.is_empty()),
+            (MergeField::Version, self.version.clone(), Some(version), !is_downgrade),
+            (MergeField::Source, self.source.clone(), Some(url_data.source.clone()), true),
+            (MergeField::Summary, self.summary.clone(), url_data.summary.clone(), self.summary.is_empty()),
+            (MergeField::Description, self.description.clone(), description, self.description.is_empty()),
+            (MergeField::Homepage, self.homepage.clone().unwrap_or_default(), url_data.homepage.clone(), self.homepage.is_none()),
+            (MergeField::License, self.license.clone(), url_data.license.clone(), self.license == "Unknown"),
+            (MergeField::BuildSys, self.build_sys.clone(), url_data.build_sys.clone(), self.build_sys == "None")
+        ];
+
+        choices.into_iter()
+            .filter_map(|(field, current, detected, accept)| {
+                detected.filter(|detected| !detected.is_empty() && *detected != current)
+                    .map(|detected| MergeChoice {field, current, detected, accept})
+            })
+            .collect()
     }
 
-    fn tarball_data(&self) -> Rc<Vec<u8>> {
-        if self.tarball_data.borrow().is_some() {
-            self.tarball_data.borrow().as_ref().unwrap().clone()
-        }
-        else {
-            let mut buffer = Vec::new();
-            reqwest::blocking::get(&self.source).unwrap().copy_to(&mut buffer).unwrap();
-            let rc = Rc::new(buffer);
-            *self.tarball_data.borrow_mut() = Some(rc.clone());
-
-            rc
+    fn apply_merge(&mut self, url_data: PkgDataUrl, choices: &[MergeChoice]) {
+        self.upstream_descriptions = url_data.descriptions;
+        self.component_signals = url_data.component_signals.clone();
+
+        for choice in choices.iter().filter(|choice| choice.accept) {
+            let value = choice.detected.clone();
+            match choice.field {
+                MergeField::Name => self.name = value,
+                MergeField::Version => {
+                    self.version = value;
+                    self.changelog = url_data.changelog.clone();
+                    for cve in self.changelog.iter().flat_map(|changelog| changelog.cves.iter()) {
+                        if !self.cves.contains(cve) {
+                            self.cves.push(cve.clone());
+                        }
+                    }
+                    self.cves.sort();
+                }
+                MergeField::Source => {
+                    self.set_source(value);
+                    self.tarball_data = RefCell::new(url_data.tarball_data.clone().map(Arc::new));
+                }
+                MergeField::Summary => self.summary = value,
+                MergeField::Description => self.description = value,
+                MergeField::Homepage => self.homepage = Some(value),
+                MergeField::License => self.license = value,
+                MergeField::BuildSys => self.build_sys = value
+            }
         }
     }
 }
 
 
 
-fn to_u8s(x: u32) -> [u8;4] {
-    let b1 : u8 = ((x >> 24) & 0xff) as u8;
-    let b2 : u8 = ((x >> 16) & 0xff) as u8;
-    let b3 : u8 = ((x >> 8) & 0xff) as u8;
-    let b4 : u8 = (x & 0xff) as u8;
-    return [b1, b2, b3, b4]
+#[derive(Clone, Copy, PartialEq)]
+enum MergeField {
+    Name,
+    Version,
+    Source,
+    Summary,
+    Description,
+    Homepage,
+    License,
+    BuildSys
+}
+
+impl MergeField {
+    fn label(self) -> &'static str {
+        match self {
+            MergeField::Name => "Name",
+            MergeField::Version => "Version",
+            MergeField::Source => "URL",
+            MergeField::Summary => "Summary",
+            MergeField::Description => "Description",
+            MergeField::Homepage => "Homepage",
+            MergeField::License => "License",
+            MergeField::BuildSys => "Build Sys"
+        }
+    }
 }
 
-fn calc_sha(buffer: &Vec<u8>) -> String  {
-    let sha_u32 = digest(&buffer);
-    let sha_u8 = [to_u8s(sha_u32[0]), to_u8s(sha_u32[1]), to_u8s(sha_u32[2]), to_u8s(sha_u32[3]), to_u8s(sha_u32[4]), to_u8s(sha_u32[5]), to_u8s(sha_u32[6]), to_u8s(sha_u32[7])].concat();
+// A value From URL found, to be accepted or not
+struct MergeChoice {
+    field: MergeField,
+    current: String,
+    detected: String,
+    accept: bool
+}
 
-    hex::encode(&sha_u8[..])
+impl MergeChoice {
+    // There's something there that would be lost, placeholders don't count
+    fn overwrites(&self) -> bool {
+        !self.current.is_empty() && !(self.field == MergeField::License && self.current == "Unknown") && !(self.field == MergeField::BuildSys && self.current == "None")
+    }
 }
 
 #[derive(Debug)]
-struct PkgDataUrl {
+pub struct PkgDataUrl {
     name: String,
     version: String,
     source: String,
     summary: Option<String>,
+    description: Option<String>,
+    // Every description found, with where it came from
+    descriptions: Vec<(String, String)>,
+    homepage: Option<String>,
     license: Option<String>,
     build_sys: Option<String>,
-    tarball_data: Option<Vec<u8>>
-}
-
-#[derive(Deserialize)]
-struct RepoApiLicense {
-    spdx_id: String
-}
-
-#[derive(Deserialize)]
-struct RepoApiCall {
-    description: String,
-    license: RepoApiLicense
-}
-
-fn ask_gh_api_repo(author: &str, repo: &str) -> RepoApiCall{
-    let gh_api = Url::parse("https://api.github.com/repos/").unwrap();
-    let gh_api = gh_api.join(&(author.to_owned() + "/")).unwrap();
-    let gh_api = gh_api.join(repo).unwrap();
-    let client = reqwest::blocking::Client::new();
-
-    let api_call_resp = client.get(gh_api).header("User-Agent", "curl/7.37.0").send().unwrap();
-    api_call_resp.json().unwrap()
-}
-
-fn guess_summary (org_url: &Url) -> Option<String> {
-    if let Some(host_str) = org_url.host_str() {
-        match host_str {
-            "github.com" => {
-                let mut segments = org_url.path_segments().unwrap();
-                if let Some(repo) = segments.clone().nth(1) {
-                    let author_name = segments.nth(0).unwrap();
-                    let resp = ask_gh_api_repo(author_name, repo);
-                    Some(resp.description)
-
-                }
-                else {
-                    None
-                }
-            }
-            _ => None
-        }
-    }
-    else {
-        None
-    }
-}
-
-
-fn guess_license_from_url(org_url: &Url) -> Option<String> {
-    if let Some(host_str) = org_url.host_str() {
-        match host_str {
-            "github.com" => {
-                let mut segments = org_url.path_segments().unwrap();
-                if let Some(repo) = segments.clone().nth(1) {
-                    let author_name = segments.nth(0).unwrap();
-                    let resp = ask_gh_api_repo(author_name, repo);
-                    Some(update_license_id(resp.license.spdx_id))
-
-                }
-                else {
-                    None
-                }
-            }
-            _ => None
-        }
-    }
-    else {
-        None
-    }
-
+    tarball_data: Option<Vec<u8>>,
+    component_signals: ComponentSignals,
+    changelog: Option<Changelog>,
+    meta_error: Option<MetadataError>,
+    // The version in the URL as it is, when it can't be made a Solus one
+    version_error: Option<(String, VersionError)>
 }
 
-fn update_license_id(id: String) -> String {
-    match id.as_str() {
-        "GPL-3.0" => "GPL-3.0-or-later".to_string(),
-        _ => id
-    }
+fn parse_url(url_str: &str) -> Result<Url> {
+    Url::parse(url_str.trim()).map_err(|err| Error::parse(format!("\"{}\" is not a valid URL: {}", url_str, err)))
 }
 
-fn from_url(url_str: &str) -> PkgDataUrl {
-    let url = Url::parse(url_str).unwrap();
-    let url_kind = url_kind_analyze(url_str);
+// old_version is the one being updated from, if any, to find what changed since
+fn from_url(url_str: &str, old_version: Option<String>, config: &Config, job: &Job) -> Result<PkgDataUrl> {
+    // Mirrors and redirectors are replaced by where they point to
+    let url = mirrors::canonical(&parse_url(url_str)?, job);
+    let url_str = url.as_str();
+    let url_kind = url_kind_analyze(url_str)?;
     let url_parser = Regex::new(r"(?P<name>\D\w+)?-?\s*(?P<version>\d+\.?(?:\d+\.)?\d+?)?").unwrap();
-    let url_segments = url.path_segments().ok_or_else(|| "cannot be base").unwrap();
-    let whole_name = url_segments.clone().last().unwrap();
+    let url_segments = url.path_segments().ok_or_else(|| Error::parse(format!("\"{}\" doesn't point to a file or repo", url_str)))?;
+    let whole_name = url_segments.clone().last().unwrap_or("");
 
+    // Every part of the regex is optional, so it always matches
     let captures = url_parser.captures(whole_name).unwrap();
     let name = {
         let match_str = captures.name("name").map_or("", |reg_match| reg_match.as_str());
@@ -395,19 +827,34 @@ fn from_url(url_str: &str) -> PkgDataUrl {
         }
     };
 
-    let (version, summary, license, build_sys, tarball_data) = match url_kind {
+    // Upstream is asked just once for everything it knows
+    job.step("Asking upstream about the project");
+    let (meta, meta_error) = match crate::metadata::fetch_for(&url, config) {
+        Some(Ok(meta)) => (meta, None),
+        Some(Err(err)) => (UpstreamMeta::default(), Some(err)),
+        None => (UpstreamMeta::default(), None)
+    };
+
+    let mut version_error = None;
+    let (version, license, build_sys, mut guess) = match url_kind {
         UrlKind::File(_) => {
             let version = captures.name("version").map_or("", |reg_match| reg_match.as_str());
-            let summary = guess_summary(&url);
-            let (license, build_sys, tarball_data) = crate::guess::try_guess_license_build_sys_for(&url);
+            let version = match Version::normalise(version) {
+                Ok(version) => version.to_string(),
+                Err(err) => {
+                    version_error = Some((version.to_string(), err));
+                    version.to_string()
+                }
+            };
+            job.check_cancelled()?;
+            let mut guess = crate::guess::try_guess_from_source(&url, job)?;
 
-            (version.to_string(), summary, license, build_sys, tarball_data)
+            (version, guess.license.take().or(meta.license), guess.build_sys.take(), guess)
         }
         UrlKind::GitRepo => {
-            let version = chrono::Utc::now().format("%Y%m%d%H%M").to_string();
-            let summary = guess_summary(&url);
-            let license = guess_license_from_url(&url);
-            (version, summary, license, None, None)
+            let last_release = meta.latest_release.as_ref().and_then(|tag| Version::normalise(tag).ok());
+            let version = Version::snapshot(last_release.as_ref(), chrono::Utc::now()).to_string();
+            (version, meta.license, None, SourceGuess::default())
         }
     };
     
@@ -415,15 +862,48 @@ fn from_url(url_str: &str) -> PkgDataUrl {
     println!("{:?}, -> {}, {}, {}, {}", whole_name, name, version, license.clone().unwrap_or("No license found".to_string()), build_sys.clone().unwrap_or("No Build System".to_string()));
 
 
-    PkgDataUrl {
+    let changelog = match old_version.filter(|old| !old.is_empty() && *old != version) {
+        Some(old) => {
+            job.check_cancelled()?;
+            job.step("Looking for what changed upstream");
+            changelog::find(&url, &config, &old, &version, guess.changelog.take())
+        }
+        None => None
+    };
+
+    // AppStream is meant for this, so it's preferred over anything else
+    let appstream = guess.appstream.unwrap_or_default();
+    let readme = guess.readme.unwrap_or_default();
+    let summary = appstream.summary.or(meta.summary).or(readme.summary);
+    let descriptions = vec![
+            ("AppStream", appstream.description),
+            ("README", readme.description),
+            ("Project page", meta.description)
+        ].into_iter()
+        .filter_map(|(origin, descr)| descr.map(|descr| (origin.to_string(), descr)))
+        .collect::<Vec<_>>();
+
+    Ok(PkgDataUrl {
         name: name.to_string(),
         version: version,
         source: url.to_string(),
         summary,
+        description: descriptions.first().map(|(_, descr)| descr.clone()),
+        descriptions,
+        homepage: meta.homepage,
         license,
         build_sys,
-        tarball_data
-    }
+        tarball_data: guess.tarball_data,
+        component_signals: ComponentSignals {
+            topics: meta.topics,
+            has_desktop_file: guess.has_desktop_file,
+            has_fonts: guess.has_fonts,
+            build_files: guess.build_files
+        },
+        changelog,
+        meta_error,
+        version_error
+    })
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -455,11 +935,13 @@ struct YamlPkg {
     #[serde(skip_serializing_if = "Option::is_none")]
     optimize: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    rundeps: Option<BTreeMap<String, String>>,
+    rundeps: Option<SubpkgList>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    replaces: Option<BTreeMap<String, String>>,
+    replaces: Option<SubpkgList>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    patterns: Option<BTreeMap<String, String>>,
+    patterns: Option<SubpkgList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     environment: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -485,21 +967,21 @@ enum UrlKind {
     File(FileKind)
 }
 
-fn url_kind_analyze(url: &str) -> UrlKind {
-    let url = Url::parse(url).unwrap();
-    let last_part = url.path_segments().unwrap().nth_back(0).unwrap();
+fn url_kind_analyze(url: &str) -> Result<UrlKind> {
+    let url = parse_url(url)?;
+    let last_part = url.path_segments().and_then(|mut segments| segments.nth_back(0)).unwrap_or("");
     let opt_ext = Path::new(last_part).extension();
 
     if let Some(ext) = opt_ext {
-        match ext.to_str().unwrap() {
-            "zip" => UrlKind::File(FileKind::Zip),
-            "git" => UrlKind::GitRepo,
-            _ => UrlKind::File(FileKind::Other)
+        match ext.to_str().unwrap_or("") {
+            "zip" => Ok(UrlKind::File(FileKind::Zip)),
+            "git" => Ok(UrlKind::GitRepo),
+            _ => Ok(UrlKind::File(FileKind::Other))
         }
     }
     else {
         // Might be other things, but right now only a Git repo is supported
-        UrlKind::GitRepo
+        Ok(UrlKind::GitRepo)
     }
 }
 
@@ -510,44 +992,82 @@ fn url_format(url: &str, kind: UrlKind) -> String {
     }
 }
 
-fn calc_sha_git(url: &str) -> String {
-    let url_parsed = Url::parse(url).unwrap();
-    let whole_name = url_parsed.path_segments().unwrap().nth_back(0).unwrap();
-    let base_name =Path::new(&whole_name).file_name().unwrap();
-    let succeed = Command::new("git").args(&["clone", url]).status().unwrap().success();
-    if succeed {
-        let stdout = Command::new("git").args(&["log", "--format=%H", "-n", "1"]).current_dir(base_name).output().unwrap().stdout;
-        std::str::from_utf8(&stdout).unwrap().to_string().trim()
-    }
-    else {
-        "".to_string()
+fn calc_sha_git(url: &str) -> Result<String> {
+    // Only the last commit is needed, there's no need to clone the whole repo
+    let output = Command::new("git").args(&["ls-remote", url, "HEAD"]).output().map_err(|err| Error::tool("git", err.to_string()))?;
+    if !output.status.success() {
+        return Err(Error::tool("git", String::from_utf8_lossy(&output.stderr).trim().to_string()));
     }
+
+    String::from_utf8_lossy(&output.stdout).split_whitespace().next()
+        .map(|sha| sha.to_string())
+        .ok_or_else(|| Error::tool("git", format!("{} has no commits", url)))
 }
 
-fn calc_sha_for(pkg_data: &PkgData, kind: UrlKind) -> String {
-    match kind {
-        UrlKind::File(_) => calc_sha(&pkg_data.tarball_data()),
-        UrlKind::GitRepo => calc_sha_git(&pkg_data.source)
+// The checksum of a source and what upstream says about it
+#[derive(Debug, Clone)]
+pub struct SourceCheck {
+    source: String,
+    sha: String,
+    report: IntegrityReport,
+    // The user already saw the report and went on
+    accepted: bool
+}
+
+impl SourceCheck {
+    fn new(source: &str, sha: String, report: IntegrityReport) -> Self {
+        SourceCheck {source: source.to_string(), sha, report, accepted: false}
     }
 }
 
-impl Into<YamlPkg> for PkgData {
+// Runs in the background, the tarball is used if From URL already got it,
+// otherwise it's hashed while it downloads to a temporary file, so it's never
+// all in memory. What upstream says about the tarball (checksums, signatures)
+// is checked too.
+fn calc_source_sha(source: &str, cached: Option<Arc<Vec<u8>>>, keyring: Option<&Path>, job: &Job) -> Result<SourceCheck> {
+    match url_kind_analyze(source)? {
+        UrlKind::File(_) => {
+            let file_name = source.rsplit('/').next().unwrap_or("source");
+            let mut tarball_file = worker::temp_file(file_name)?;
+            let sha = match &cached {
+                Some(tarball_data) => {
+                    job.step("Calculating checksum");
+                    tarball_file.write_all(&tarball_data[..])?;
+                    hashing::sha256(&tarball_data)
+                }
+                None => {
+                    let mut writer = hashing::HashingWriter::new(hashing::Algorithm::Sha256, tarball_file.as_file_mut());
+                    job.download_to(source, &mut writer)?;
+                    writer.finish().0
+                }
+            };
+            let report = integrity::verify(source, tarball_file.path(), &sha, keyring, job)?;
+            Ok(SourceCheck::new(source, sha, report))
+        }
+        UrlKind::GitRepo => {
+            job.step("Asking git for the last commit");
+            Ok(SourceCheck::new(source, calc_sha_git(source)?, IntegrityReport::default()))
+        }
+    }
+}
 
-    fn into(self) -> YamlPkg {
+impl PkgData {
+    // sha is the one of the source, made by calc_source_sha
+    fn into_yaml(self, sha: String) -> Result<YamlPkg> {
+        let pkg_data = self;
         let mut bmap = BTreeMap::new();
-        let url_kind = url_kind_analyze(&self.source);
-        let sha = calc_sha_for(&self, url_kind);
-        let url_formatted = url_format(&self.source, url_kind);
+        let url_kind = url_kind_analyze(&pkg_data.source)?;
+        let url_formatted = url_format(&pkg_data.source, url_kind);
         bmap.insert(url_formatted, sha);
 
         let empty = ("".to_string(), "".to_string(), "".to_string());
-        let (setup_str, build_str, install_str) = match self.build_sys.as_str() {
+        let (setup_str, build_str, install_str) = match pkg_data.build_sys.as_str() {
             "Meson" => ("%meson_configure".to_string(), "%ninja_build".to_string(), "%ninja_install".to_string()),
             "Configure & Make" => ("%configure".to_string(), "%make".to_string(), "%make_install".to_string()),
             "CMake & Make" => ("%cmake".to_string(), "%make".to_string(), "%make_install".to_string()),
             "CMake & Ninja" => ("%cmake_ninja".to_string(), "%ninja_build".to_string(), "%ninja_install".to_string()),
             "Unknown" => {
-                if let Some(org_yaml) = self.org_yaml.clone() {
+                if let Some(org_yaml) = pkg_data.org_yaml.clone() {
                     (org_yaml.setup, org_yaml.build, org_yaml.install)
                 }
                 else {
@@ -556,42 +1076,59 @@ impl Into<YamlPkg> for PkgData {
             }
             _ => empty
         };
+        // The %patch lines are only rewritten if the patches changed, so that
+        // they keep whatever -p they had
+        let setup_str = {
+            if patches::applied_patches(&setup_str) != pkg_data.patches {
+                patches::set_patches(&setup_str, &pkg_data.patches)
+            }
+            else {
+                setup_str
+            }
+        };
 
+        // Same for subpackages, untouched lists keep their order and layout
+        let (patterns, rundeps, replaces) = match pkg_data.org_yaml.clone() {
+            Some(org_yaml) if subpackages::same(&subpackages::from_lists(org_yaml.patterns.clone(), org_yaml.rundeps.clone(), org_yaml.replaces.clone()), &pkg_data.subpackages) => {
+                (org_yaml.patterns, org_yaml.rundeps, org_yaml.replaces)
+            }
+            _ => subpackages::to_lists(&pkg_data.subpackages)
+        };
 
-
-        YamlPkg {
-            name: self.name,
-            version: self.version,
-            release: self.release,
+        Ok(YamlPkg {
+            name: pkg_data.name,
+            version: pkg_data.version,
+            release: pkg_data.release,
             source: vec![bmap],
-            license: self.license,
-            component: self.component,
-            summary: self.summary,
-            description: self.description,
-            builddeps: self.org_yaml.clone().map_or(Vec::new(), |yaml| yaml.builddeps),
+            license: pkg_data.license,
+            component: pkg_data.component,
+            summary: pkg_data.summary,
+            description: pkg_data.description,
+            builddeps: pkg_data.build_deps,
             setup: setup_str.to_string(),
             build: build_str.to_string(),
             install: install_str.to_string(),
 
             //Optional Keys
-            clang: self.org_yaml.clone().map_or(None, |yaml| yaml.clang),
-            extract: self.org_yaml.clone().map_or(None, |yaml| yaml.extract),
-            autodep: self.org_yaml.clone().map_or(None, |yaml| yaml.autodep),
-            emul32: self.org_yaml.clone().map_or(None, |yaml| yaml.emul32),
-            libsplit: self.org_yaml.clone().map_or(None, |yaml| yaml.libsplit),
-            optimize: self.org_yaml.clone().map_or(None, |yaml| yaml.optimize),
-            rundeps: self.org_yaml.clone().map_or(None, |yaml| yaml.rundeps),
-            replaces: self.org_yaml.clone().map_or(None, |yaml| yaml.replaces),
-            patterns: self.org_yaml.clone().map_or(None, |yaml| yaml.patterns),
-            environment: self.org_yaml.clone().map_or(None, |yaml| yaml.environment),
-            networking: self.org_yaml.clone().map_or(None, |yaml| yaml.networking),
+            clang: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.clang),
+            extract: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.extract),
+            autodep: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.autodep),
+            emul32: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.emul32),
+            libsplit: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.libsplit),
+            optimize: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.optimize),
+            rundeps,
+            replaces,
+            homepage: pkg_data.homepage,
+            patterns,
+            environment: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.environment),
+            networking: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.networking),
 
 
             // Build steps, optional
-            check: self.org_yaml.clone().map_or(None, |yaml| yaml.check),
-            profile: self.org_yaml.map_or(None, |yaml| yaml.profile),
+            check: pkg_data.org_yaml.clone().map_or(None, |yaml| yaml.check),
+            profile: pkg_data.org_yaml.map_or(None, |yaml| yaml.profile),
 
-        }
+        })
     }
 }
 impl From<YamlPkg> for PkgData {
@@ -630,7 +1167,7 @@ impl From<YamlPkg> for PkgData {
 
         let yaml_copy = yaml.clone();
 
-        let (url_str, _) = yaml.source.first().unwrap().iter().nth(0).unwrap();
+        let url_str = yaml.source.first().and_then(|source| source.keys().next()).cloned().unwrap_or_default();
         PkgData {
             name: yaml.name,
             version: yaml.version,
@@ -640,32 +1177,145 @@ impl From<YamlPkg> for PkgData {
             component: yaml.component,
             summary: yaml.summary,
             description: yaml.description,
+            homepage: yaml.homepage,
+            upstream_descriptions: Vec::new(),
+            component_signals: ComponentSignals::default(),
             build_sys:  build_sys.to_string(),
             org_yaml: Some(yaml_copy),
             file_path: None,
+            file_text: None,
+            release_info: None,
+            release_edited: false,
             tarball_data: RefCell::new(None),
+            source_check: None,
+            patches: patches::applied_patches(&yaml.setup),
+            patch_files: PendingFiles::default(),
+            subpackages: subpackages::from_lists(yaml.patterns, yaml.rundeps, yaml.replaces),
+            changelog: None,
+            cves: Vec::new(),
             build_deps: yaml.builddeps
         }
 
     }
 }
 
+fn load_pkg(pkg_path: &Path) -> Result<PkgData> {
+    let pkg_str = std::fs::read_to_string(pkg_path)?;
+    let pkg_yaml: YamlPkg = serde_yaml::from_str(&pkg_str)?;
+    let mut pkg_data: PkgData = pkg_yaml.into();
+    pkg_data.release_info = Some(ReleaseInfo::read(pkg_path, pkg_data.release));
+    pkg_data.file_path = Some(pkg_path.to_path_buf());
+    pkg_data.file_text = Some(pkg_str);
+
+    Ok(pkg_data)
+}
+
+// The old file is only replaced once the new one is completely written, so
+// that a failure midway doesn't leave a truncated package.yml
+fn write_atomic(file_path: &Path, text: &str, backup: bool) -> Result<()> {
+    let file_name = file_path.file_name().map_or("package.yml".into(), |name| name.to_string_lossy());
+    let tmp_path = file_path.with_file_name(format!(".{}.tmp", file_name));
+    let written = std::fs::File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(text.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err.into());
+    }
+
+    if backup && file_path.is_file() {
+        std::fs::copy(file_path, file_path.with_file_name(format!("{}.bak", file_name)))?;
+    }
+
+    std::fs::rename(&tmp_path, file_path)?;
+    Ok(())
+}
+
+// What saving would write
+fn render_pkg(pkg_data: &PkgData, sha: String) -> Result<String> {
+    let yaml = pkg_data.clone().into_yaml(sha)?;
+    Ok(serde_yaml::to_string(&yaml)?)
+}
+
+// Writes text made by render_pkg and returns where. With no target the file
+// it was loaded from is used, or a new one under the packages root. Unless the
+// target was chosen with "Save As" (where GTK already asked) a file that
+// isn't the one loaded is never overwritten.
+fn save_pkg(pkg_data: &PkgData, text: &str, target: Option<PathBuf>, config: &Config) -> Result<PathBuf> {
+    let chosen = target.is_some();
+    let file_path = match target.or_else(|| pkg_data.file_path.clone()) {
+        Some(file_path) => file_path,
+        None => config.packages_root().join(&pkg_data.name).join("package.yml")
+    };
+
+    if !chosen && file_path.is_file() {
+        let on_disk = std::fs::read_to_string(&file_path)?;
+        if pkg_data.file_text.as_ref() != Some(&on_disk) {
+            return Err(Error::ChangedOnDisk(file_path));
+        }
+    }
+
+    if let Some(pkg_path) = file_path.parent() {
+        if !pkg_path.is_dir() {
+            std::fs::create_dir_all(pkg_path)?;
+        }
+    }
+
+    write_atomic(&file_path, text, config.backup)?;
+    if let Some(pkg_path) = file_path.parent() {
+        pkg_data.patch_files.apply(pkg_path)?;
+    }
+
+    Ok(file_path)
+}
+
 #[derive(Msg)]
 pub enum WinMsg {
     Quit,
     NameChanged(String),
     VersionChanged(String),
+    ReleaseChanged(u16),
+    BumpRelease,
     UrlChanged(String),
-    LicenseChanged(String),
-    ComponentChanged(String),
-    BuildSysChanged(String),
+    LicenseChanged(Option<String>),
+    ComponentChanged(Option<String>),
+    UseComponent(String),
+    BuildSysChanged(Option<String>),
     SummaryChanged(String),
     DescriptionChanged,
+    DescrSourceChanged(Option<String>),
+    SubpkgSelected(Option<String>),
+    AddSubpkg,
+    SubpkgChanged,
     ShowBuildDeps,
+    ShowPatches,
     New,
     LoadFile,
     FromUrl,
-    StartMaking
+    StartMaking,
+    SaveAs,
+    Undo,
+    Redo,
+    KeyPressed(gdk::enums::key::Key, gdk::ModifierType),
+    // Sent by the work going on in the background
+    WorkProgress(String, Option<f64>),
+    UrlAnalyzed(Result<PkgDataUrl>),
+    ShaCalculated(Result<SourceCheck>),
+    OpenRepo,
+    OpenWorkspace(PathBuf),
+    WorkspaceScanned(Result<(PathBuf, Vec<PackageEntry>)>),
+    ComponentsLoaded(Vec<PkgComponent>),
+    FilterChanged(String),
+    OpenPackage(i32),
+    TabSwitched(u32),
+    CloseTab(u64),
+    Commit,
+    EditCves,
+    Build,
+    BuildLog(String),
+    BuildFinished(Result<BuildReport>),
+    CancelWork
 }
 
 impl Win {
@@ -673,24 +1323,494 @@ impl Win {
         let buffer = self.txt_descr.get_buffer().unwrap();
         buffer.set_text(&self.model.pkg_data.description);
     }
+    fn update_descr_sources(&self) {
+        self.cmb_descr_source.remove_all();
+        for (origin, _) in self.model.pkg_data.upstream_descriptions.iter() {
+            self.cmb_descr_source.append(Some(origin), origin);
+        }
+        self.cmb_descr_source.set_sensitive(!self.model.pkg_data.upstream_descriptions.is_empty());
+    }
     fn update_txt_and_combos(&mut self) {
-        self.cmb_license.set_active_id(Some(&self.model.pkg_data.license));
-        self.cmb_buildsys.set_active_id(Some(&self.model.pkg_data.build_sys));
-        self.cmb_component.set_active_id(Some(&self.model.pkg_data.component));
+        // Values we don't know about (e.g: some other license) are added so they aren't lost
+        fn set_combo_value(cmb: &ComboBoxText, value: &str) {
+            if !cmb.set_active_id(Some(value)) {
+                cmb.append(Some(value), value);
+                cmb.set_active_id(Some(value));
+            }
+        }
+
+        set_combo_value(&self.cmb_license, &self.model.pkg_data.license);
+        set_combo_value(&self.cmb_buildsys, &self.model.pkg_data.build_sys);
+        set_combo_value(&self.cmb_component, &self.model.pkg_data.component);
         self.update_descr();
+        self.update_descr_sources();
+    }
+    fn show_progress(&self, text: &str, fraction: Option<f64>) {
+        self.progress.set_text(Some(text));
+        match fraction {
+            Some(fraction) => self.progress.set_fraction(fraction.min(1.0)),
+            None => self.progress.pulse()
+        }
+    }
+    fn start_work(&mut self, job: CancelHandle, text: &str) {
+        self.model.job = Some(job);
+        self.model.header.emit(HeaderMsg::Busy(true));
+        self.progress.set_fraction(0.0);
+        self.show_progress(text, None);
+    }
+    // Checks before saving, then the checksum is calculated in the background
+    // and the saving ends in ShaCalculated. Returns whether it started.
+    fn start_saving(&mut self) -> bool {
+        let downgrade = self.model.pkg_data.org_yaml.as_ref().and_then(|org_yaml| version_downgrade(&org_yaml.version, &self.model.pkg_data.version));
+        let go_on = downgrade.map_or(true, |(old, new)| downgrade_dialog(&self.window, &old, &new));
+
+        if !go_on {
+            // Nothing to do, the user cancelled the save
+            false
+        }
+        else if self.model.can_start {
+            let source = self.model.pkg_data.source.clone();
+            let cached = self.model.pkg_data.tarball_data.borrow().clone();
+            // A file that was already checked is the same until the source changes
+            let checked = self.model.pkg_data.source_check.clone().filter(|check| check.source == source);
+            let keyring = self.model.config.keyring.clone();
+            let job = worker::spawn(self.model.sender.clone(), WorkProgress, move |job| match checked {
+                Some(check) => Ok(check),
+                None => calc_source_sha(&source, cached, keyring.as_deref(), job)
+            }, ShaCalculated);
+            self.start_work(job, "Calculating checksum");
+            true
+        }
+        else {
+            cant_start_dialog(&self.window, &self.model.diagnostics);
+            false
+        }
+    }
+    fn on_url_analyzed(&mut self, mut url_data: PkgDataUrl) {
+        if let Some(err) = url_data.meta_error.take() {
+            warning_dialog(&self.window, &format!("Couldn't get the project data from upstream: {}", err));
+        }
+        if let Some((version, err)) = url_data.version_error.take() {
+            warning_dialog(&self.window, &format!("Couldn't make a Solus version out of \"{}\" ({}), check it by hand", version, err));
+        }
+
+        // Only ask when something already there would be replaced
+        let mut choices = self.model.pkg_data.merge_choices(&url_data);
+        if choices.iter().any(MergeChoice::overwrites) && !merge_dialog(&self.window, &mut choices) {
+            return;
+        }
+
+        // Everything From URL changes is undone at once
+        self.record_edit(None);
+        self.model.pkg_data.apply_merge(url_data, &choices);
+        self.refresh_form();
+    }
+    fn set_saved(&mut self, saved: bool) {
+        self.model.saved = saved;
+        self.model.header.emit(if saved {HeaderMsg::FileSaved} else {HeaderMsg::FileModified});
+    }
+    // Call before changing pkg_data, edit joins steps (see History::record)
+    fn record_edit(&mut self, edit: Option<&'static str>) {
+        self.model.history.record(&self.model.pkg_data, edit);
+        self.set_saved(false);
+        self.model.pkg_data.bump_release_once();
+        self.spin_release.set_value(self.model.pkg_data.release as f64);
+    }
+    // Going back in history doesn't go back on what's on disk (the releases
+    // already committed included) nor on the CVEs, which aren't edits of the
+    // package itself. Every snapshot is of the same file, New and loading
+    // start a new history.
+    fn restored(&self, mut pkg_data: PkgData) -> PkgData {
+        pkg_data.file_path = self.model.pkg_data.file_path.clone();
+        pkg_data.file_text = self.model.pkg_data.file_text.clone();
+        pkg_data.release_info = self.model.pkg_data.release_info;
+        pkg_data.cves = self.model.pkg_data.cves.clone();
+        pkg_data
+    }
+    // Fields with problems are marked, the problems are in the tooltip
+    fn show_diagnostics(&self) {
+        fn mark<W: IsA<gtk::Widget>>(widget: &W, diags: &[&Diagnostic]) {
+            let style = widget.get_style_context();
+            let worst = diags.iter().map(|diag| diag.severity).fold(None, |worst: Option<Severity>, sev| Some(worst.map_or(sev, |worst| if sev > worst {sev} else {worst})));
+            style.remove_class("error");
+            style.remove_class("warning");
+            match worst {
+                Some(Severity::Error) => style.add_class("error"),
+                Some(Severity::Warning) => style.add_class("warning"),
+                None => {}
+            }
+
+            let text = diags.iter().map(|diag| diag.message.clone()).collect::<Vec<_>>().join("\n");
+            widget.set_tooltip_text(if text.is_empty() {None} else {Some(&text)});
+        }
+
+        fn mark_entry(entry: &gtk::Entry, diags: &[&Diagnostic]) {
+            mark(entry, diags);
+            let icon = match diags.iter().any(|diag| diag.severity == Severity::Error) {
+                true => "dialog-error-symbolic",
+                false => "dialog-warning-symbolic"
+            };
+            entry.set_icon_from_icon_name(gtk::EntryIconPosition::Secondary, if diags.is_empty() {None} else {Some(icon)});
+        }
+
+        let of = |field: Field| self.model.diagnostics.iter().filter(|diag| diag.field == field).collect::<Vec<_>>();
+        mark_entry(&self.ent_name, &of(Field::Name));
+        mark_entry(&self.ent_version, &of(Field::Version));
+        mark_entry(&self.ent_source, &of(Field::Source));
+        mark_entry(&self.ent_summary, &of(Field::Summary));
+        mark(&self.cmb_license, &of(Field::License));
+        mark(&self.cmb_component, &of(Field::Component));
+        mark(&self.txt_descr, &of(Field::Description));
+    }
+    fn fill_components(&self) {
+        self.cmb_component.remove_all();
+        for component in self.model.components.iter() {
+            self.cmb_component.append(Some(&component.name), &component.label());
+        }
+        // One that isn't in the list isn't lost
+        let component = &self.model.pkg_data.component;
+        if !self.cmb_component.set_active_id(Some(component)) {
+            self.cmb_component.append(Some(component), component);
+            self.cmb_component.set_active_id(Some(component));
+        }
+    }
+    fn update_component_suggestions(&mut self) {
+        let suggested = components::suggest(&self.model.pkg_data.build_deps, &self.model.pkg_data.component_signals, &self.model.components).into_iter()
+            .filter(|suggested| *suggested != self.model.pkg_data.component)
+            .take(MAX_SUGGESTIONS)
+            .collect::<Vec<_>>();
+        if suggested == self.model.suggested_components {
+            return;
+        }
+
+        for child in self.box_suggestions.get_children() {
+            self.box_suggestions.remove(&child);
+        }
+        for component in suggested.iter() {
+            let button = gtk::Button::new_with_label(component);
+            let sender = self.model.sender.clone();
+            let component = component.clone();
+            button.connect_clicked(move |_| {let _ = sender.send(UseComponent(component.clone()));});
+            self.box_suggestions.add(&button);
+        }
+        self.box_suggestions.show_all();
+        self.model.suggested_components = suggested;
+    }
+    fn subpkg_views(&self) -> [&gtk::TextView; 3] {
+        [&self.txt_patterns, &self.txt_rundeps, &self.txt_replaces]
+    }
+    fn update_subpkgs(&mut self) {
+        if !self.model.pkg_data.subpackages.contains_key(&self.model.subpkg) {
+            self.model.subpkg = String::new();
+        }
+
+        self.cmb_subpkg.remove_all();
+        self.cmb_subpkg.append(Some(""), MAIN_SUBPKG_LABEL);
+        for name in self.model.pkg_data.subpackages.keys().filter(|name| !name.is_empty()) {
+            self.cmb_subpkg.append(Some(name), name);
+        }
+        self.cmb_subpkg.set_active_id(Some(&self.model.subpkg));
+
+        let subpkg = self.model.pkg_data.subpackages.get(&self.model.subpkg).cloned().unwrap_or_default();
+        for (view, values) in self.subpkg_views().iter().zip([subpkg.patterns, subpkg.rundeps, subpkg.replaces].iter()) {
+            view.get_buffer().unwrap().set_text(&values.join("\n"));
+        }
+    }
+    // One value per line
+    fn read_subpkg(&self) -> Subpackage {
+        let lines = |view: &gtk::TextView| {
+            let buffer = view.get_buffer().unwrap();
+            let text = buffer.get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false).unwrap().to_string();
+            text.lines().map(|line| line.trim().to_string()).filter(|line| !line.is_empty()).collect::<Vec<_>>()
+        };
+
+        Subpackage {
+            patterns: lines(&self.txt_patterns),
+            rundeps: lines(&self.txt_rundeps),
+            replaces: lines(&self.txt_replaces)
+        }
+    }
+    fn log_buffer(&self) -> gtk::TextBuffer {
+        self.txt_log.get_buffer().unwrap()
+    }
+    fn append_log(&self, line: &str) {
+        let buffer = self.log_buffer();
+        buffer.insert(&mut buffer.get_end_iter(), &format!("{}\n", line));
+        self.txt_log.scroll_to_mark(&buffer.get_insert().unwrap(), 0.0, false, 0.0, 1.0);
+    }
+    fn show_build_report(&self, report: &BuildReport) {
+        let names = report.artifacts.iter()
+            .map(|path| path.file_name().map_or(path.display().to_string(), |name| name.to_string_lossy().to_string()))
+            .collect::<Vec<_>>();
+        let (kind, title) = if report.success {
+            (gtk::MessageType::Info, format!("{} finished", report.tool.name()))
+        }
+        else {
+            (gtk::MessageType::Error, format!("{} failed ({}), see the build log", report.tool.name(), report.status))
+        };
+        self.append_log(&title);
+
+        let dialog = gtk::MessageDialog::new::<gtk::Window>(Some(&self.window), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, kind, gtk::ButtonsType::Ok, &title);
+        if !names.is_empty() {
+            dialog.set_property_secondary_text(Some(&format!("Made or changed:\n{}", names.join("\n"))));
+        }
+        dialog.run();
+        dialog.destroy();
+    }
+    fn commit(&mut self, file_path: &Path) {
+        let dir = file_path.parent().unwrap_or_else(|| Path::new("."));
+        if git::repo_root(dir).is_none() {
+            warning_dialog(&self.window, &format!("{} isn't in a git repository", dir.display()));
+            return;
+        }
+
+        let diff = match git::diff(dir) {
+            Ok(diff) if diff.trim().is_empty() => return warning_dialog(&self.window, "Nothing changed since the last commit"),
+            Ok(diff) => diff,
+            Err(err) => return error_dialog(&self.window, "Couldn't get the changes", &err)
+        };
+
+        let old = git::committed_file(file_path).and_then(|text| serde_yaml::from_str::<YamlPkg>(&text).ok()).map(PkgData::from);
+        let notes_url = parse_url(&self.model.pkg_data.source).ok().and_then(|url| metadata::release_notes_url(&url));
+        let message = git::commit_message(old.as_ref(), &self.model.pkg_data, notes_url.as_deref());
+
+        if let Some((message, write_advisory)) = commit_dialog(&self.window, &diff, &message, !self.model.pkg_data.cves.is_empty()) {
+            match git::commit(dir, &message) {
+                Ok(()) => {
+                    // What's committed is the new base for release bumps
+                    self.model.pkg_data.release_info = Some(ReleaseInfo::read(file_path, self.model.pkg_data.release));
+                    self.model.pkg_data.release_edited = false;
+                    if write_advisory {
+                        let commit = git::head(dir).ok();
+                        if let Err(err) = advisory::write(&self.model.pkg_data, commit.as_deref(), &self.model.config) {
+                            error_dialog(&self.window, "Couldn't write the advisory", &err);
+                        }
+                    }
+                }
+                Err(err) => error_dialog(&self.window, "Couldn't commit", &err)
+            }
+        }
+    }
+    // Offers to add what the failed build said it was missing
+    fn suggest_missing_deps(&mut self) {
+        let buffer = self.log_buffer();
+        let log = buffer.get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false).unwrap().to_string();
+        let added = build_log::show_missing(&self.window, &build_log::analyze(&log), &self.model.pkg_data.build_deps);
+        if !added.is_empty() {
+            self.record_edit(None);
+            self.model.pkg_data.build_deps.extend(added);
+        }
+    }
+    // Adds a tab for the package in stored, it isn't shown yet
+    fn add_tab(&mut self, stored: TabState) -> usize {
+        let page = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let label = gtk::Label::new(Some("Untitled"));
+        let close = gtk::Button::new_from_icon_name(Some("window-close-symbolic"), gtk::IconSize::Menu);
+        close.set_relief(gtk::ReliefStyle::None);
+
+        let id = self.model.next_tab_id;
+        self.model.next_tab_id += 1;
+        let sender = self.model.sender.clone();
+        close.connect_clicked(move |_| {let _ = sender.send(CloseTab(id));});
+
+        let tab_label = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        tab_label.pack_start(&label, false, false, 0);
+        tab_label.pack_start(&close, false, false, 0);
+        tab_label.show_all();
+        page.show();
+        self.tabs.append_page(&page, Some(&tab_label));
+
+        self.model.tabs.push(Tab {id, label, stored: Some(stored)});
+        self.model.tabs.len() - 1
+    }
+    fn stash_tab(&mut self) {
+        let state = TabState {
+            pkg_data: std::mem::replace(&mut self.model.pkg_data, PkgData::new()),
+            history: std::mem::replace(&mut self.model.history, History::new()),
+            saved: self.model.saved,
+            subpkg: std::mem::replace(&mut self.model.subpkg, String::new())
+        };
+        self.model.tabs[self.model.tab].stored = Some(state);
+    }
+    // Puts the package of tabs[index] in the editor, the current one has to be stashed first
+    fn show_tab(&mut self, index: usize) {
+        if let Some(state) = self.model.tabs[index].stored.take() {
+            self.model.pkg_data = state.pkg_data;
+            self.model.history = state.history;
+            self.model.subpkg = state.subpkg;
+            self.model.tab = index;
+            self.refresh_form();
+            self.set_saved(state.saved);
+            self.tabs.set_current_page(Some(index as u32));
+        }
+    }
+    fn switch_tab(&mut self, index: usize) {
+        if index != self.model.tab && index < self.model.tabs.len() {
+            self.stash_tab();
+            self.show_tab(index);
+        }
+    }
+    fn update_tab_label(&self) {
+        if let Some(tab) = self.model.tabs.get(self.model.tab) {
+            let name = &self.model.pkg_data.name;
+            tab.label.set_text(if name.is_empty() {"Untitled"} else {name});
+        }
+    }
+    fn warn_behind_pspec(&self, pkg_data: &PkgData) {
+        if let Some(pspec) = pkg_data.release_info.and_then(|info| info.behind_pspec()) {
+            warning_dialog(&self.window, &format!("package.yml says release {} but pspec_x86_64.xml already has release {}, the next change will go above it", pkg_data.release, pspec));
+        }
+    }
+    fn set_loaded(&mut self, pkg_data: PkgData) {
+        self.warn_behind_pspec(&pkg_data);
+        self.model.history.clear();
+        self.model.pkg_data = pkg_data;
+        self.model.subpkg = String::new();
+
+        self.refresh_form();
+
+        self.set_saved(true);
+    }
+    // A new package has a history of its own, like a loaded one, so that undo
+    // never mixes two files
+    fn set_new(&mut self) {
+        self.model.history.clear();
+        self.model.pkg_data = PkgData::new();
+        self.model.subpkg = String::new();
+        self.refresh_form();
+        self.set_saved(false);
+    }
+    fn close_tab(&mut self, id: u64) {
+        let index = match self.model.tabs.iter().position(|tab| tab.id == id) {
+            Some(index) => index,
+            None => return
+        };
+        let saved = self.model.tabs[index].stored.as_ref().map_or(self.model.saved, |state| state.saved);
+        if !saved && !confirm_dialog(&self.window, "This package has unsaved changes, close it anyway?") {
+            return;
+        }
+
+        // There's always a package in the editor
+        if self.model.tabs.len() == 1 {
+            self.set_new();
+            return;
+        }
+
+        self.model.tabs.remove(index);
+        if index == self.model.tab {
+            let next = index.min(self.model.tabs.len() - 1);
+            self.model.tab = next;
+            self.tabs.remove_page(Some(index as u32));
+            self.show_tab(next);
+        }
+        else {
+            if index < self.model.tab {
+                self.model.tab -= 1;
+            }
+            self.tabs.remove_page(Some(index as u32));
+        }
+    }
+    fn fill_workspace(&mut self, filter: &str) {
+        for child in self.lst_packages.get_children() {
+            self.lst_packages.remove(&child);
+        }
+
+        self.model.shown = self.model.workspace.iter().enumerate()
+            .filter(|(_, entry)| entry.matches(filter))
+            .map(|(i, _)| i)
+            .collect();
+        for entry in self.model.shown.iter().map(|i| &self.model.workspace[*i]) {
+            let text = format!("{}  {}-{}\n{} · {}", entry.name, entry.version, entry.release, entry.component, entry.modified_str());
+            let label = gtk::Label::new(Some(&text));
+            label.set_halign(gtk::Align::Start);
+            label.set_tooltip_text(Some(&entry.path.display().to_string()));
+            self.lst_packages.add(&label);
+        }
+        self.lst_packages.show_all();
+    }
+    // The tab that has file_path open, if any
+    fn tab_with_file(&self, file_path: &Path) -> Option<usize> {
+        self.model.tabs.iter().enumerate().find(|(i, tab)| {
+            let pkg_data = if *i == self.model.tab {Some(&self.model.pkg_data)} else {tab.stored.as_ref().map(|state| &state.pkg_data)};
+            pkg_data.and_then(|pkg_data| pkg_data.file_path.as_ref()).map_or(false, |path| path == file_path)
+        }).map(|(i, _)| i)
+    }
+    fn update_changelog(&self) {
+        let changelog = match &self.model.pkg_data.changelog {
+            Some(changelog) => changelog,
+            None => return self.exp_changes.hide()
+        };
+
+        // Security fixes stand out
+        let label = gtk::Label::new(None);
+        if changelog.cves.is_empty() {
+            label.set_text(&format!("Upstream changes (from {})", changelog.origin));
+        }
+        else {
+            label.set_markup(&format!("Upstream changes (from {}) <span foreground=\"#c01c28\"><b>security: {}</b></span>", changelog.origin, changelog.cves.join(", ")));
+        }
+        label.show();
+        self.exp_changes.set_label_widget(Some(&label));
+
+        let text = match &changelog.url {
+            Some(url) => format!("{}\n\n{}", changelog.text, url),
+            None => changelog.text.clone()
+        };
+        self.txt_changes.get_buffer().unwrap().set_text(&text);
+        self.exp_changes.show();
+    }
+    fn refresh_form(&mut self) {
+        self.ent_name.set_text(&self.model.pkg_data.name);
+        self.ent_version.set_text(&self.model.pkg_data.version);
+        self.spin_release.set_value(self.model.pkg_data.release as f64);
+        self.ent_source.set_text(&self.model.pkg_data.source);
+        self.ent_summary.set_text(&self.model.pkg_data.summary);
+        self.model.header.emit(HeaderMsg::NewSubtitle(self.model.pkg_data.name.clone()));
+        self.update_txt_and_combos();
+        self.update_subpkgs();
+        self.update_tab_label();
+        self.update_changelog();
     }
 }
 
 const RIGHT_COL_PROPORTION: i32 = 10;
+// How the main package is called in the subpackage editor
+const MAIN_SUBPKG_LABEL: &str = "(main package)";
+// How many component suggestions are shown
+const MAX_SUGGESTIONS: usize = 3;
+// How much of a value is shown when merging data from upstream
+const MERGE_MAX_CHARS: usize = 300;
 #[widget]
 impl Widget for Win {
-    fn model() -> Model {
+    fn model(relm: &Relm<Self>, _: ()) -> Model {
         let header = init::<Header>(()).expect("Header");
+        // The real list comes in ComponentsLoaded
+        let components = components::builtin();
+        let stream = relm.stream().clone();
+        let (channel, sender) = Channel::new(move |msg| stream.emit(msg));
 
         Model {
             header,
             pkg_data: PkgData::new(),
-            can_start: false
+            can_start: false,
+            diagnostics: lint::lint_pkg(&PkgData::new(), &components),
+            components,
+            suggested_components: Vec::new(),
+            _channel: channel,
+            sender,
+            job: None,
+            busy: false,
+            config: Config::default(),
+            save_as: None,
+            history: History::new(),
+            saved: false,
+            subpkg: String::new(),
+            tabs: Vec::new(),
+            tab: 0,
+            next_tab_id: 0,
+            workspace: Vec::new(),
+            workspace_root: None,
+            shown: Vec::new()
         }
     }
 
@@ -703,127 +1823,433 @@ impl Widget for Win {
             cmb.set_active_iter(cmb.get_model().unwrap().get_iter_first().as_ref());
         }
 
+        // Without a window there's nowhere to say it's broken, so it's read here
+        match Config::load() {
+            Ok(config) => self.model.config = config,
+            Err(err) => error_dialog(&self.window, "Ignoring the config file, using the defaults", &err)
+        }
+
         fill_combo(&self.cmb_license, LICENSES);
-        fill_combo(&self.cmb_component, COMPONENTS);
+        self.fill_components();
         fill_combo(&self.cmb_buildsys, BUILD_SYSS);
+        self.update_descr_sources();
+        self.update_subpkgs();
+        self.show_diagnostics();
+
+        // The first tab is the package already in the editor
+        let first = self.add_tab(TabState::new(PkgData::new(), false));
+        self.model.tabs[first].stored = None;
+        // Through update, so that the view knows it's busy
+        if let Some(repo_dir) = self.model.config.repo_dir.clone() {
+            let _ = self.model.sender.send(OpenWorkspace(repo_dir));
+        }
+        // Nothing is shown for it, the built-in list is there until then
+        let config = self.model.config.clone();
+        worker::spawn(self.model.sender.clone(), WorkProgress, move |_| components::load(&config), ComponentsLoaded);
 
         self.window.set_default_size(950, 600);
     }
 
     fn update(&mut self, event: WinMsg) {
+        // Only what can change the package is linted again, not every log line or progress tick
+        let pkg_changes = !matches!(event, Quit | WorkProgress(..) | BuildLog(_) | CancelWork | OpenRepo | OpenWorkspace(_) | WorkspaceScanned(_) | FilterChanged(_) | Build);
 
         match event {
             Quit => gtk::main_quit(),
-            NameChanged(name) => {
+            // Widgets also report values set from code (undo, From URL...), those
+            // are already in pkg_data and aren't edits
+            NameChanged(name) => if name != self.model.pkg_data.name {
+                self.record_edit(Some("name"));
                 self.model.header.emit(HeaderMsg::NewSubtitle(name.clone()));
-                self.model.header.emit(HeaderMsg::FileModified);
                 self.model.pkg_data.name = name.clone();
+                self.update_tab_label();
             },
-            VersionChanged(version) => {
-                self.model.header.emit(HeaderMsg::FileModified);
+            VersionChanged(version) => if version != self.model.pkg_data.version {
+                self.record_edit(Some("version"));
                 self.model.pkg_data.version = version;
             },
-            UrlChanged(url) => {
-                self.model.header.emit(HeaderMsg::FileModified);
-                self.model.pkg_data.source = url;
-                self.model.pkg_data.tarball_data = RefCell::new(None);
+            // Set by hand, so it isn't bumped on top
+            ReleaseChanged(release) => if release != self.model.pkg_data.release {
+                self.model.history.record(&self.model.pkg_data, Some("release"));
+                self.set_saved(false);
+                self.model.pkg_data.release = release;
+                self.model.pkg_data.release_edited = true;
+            },
+            BumpRelease => match self.model.pkg_data.release.checked_add(1) {
+                Some(release) => {
+                    self.model.history.record(&self.model.pkg_data, None);
+                    self.set_saved(false);
+                    self.model.pkg_data.release = release;
+                    self.model.pkg_data.release_edited = true;
+                    self.spin_release.set_value(self.model.pkg_data.release as f64);
+                }
+                None => error_dialog(&self.window, "Can't bump the release", &Error::parse(format!("{} is the highest release there can be", u16::MAX)))
+            },
+            UrlChanged(url) => if url != self.model.pkg_data.source {
+                self.record_edit(Some("source"));
+                self.model.pkg_data.set_source(url);
             },
-            LicenseChanged(license) => {
-                self.model.header.emit(HeaderMsg::FileModified);
+            LicenseChanged(Some(license)) => if license != self.model.pkg_data.license {
+                self.record_edit(None);
                 self.model.pkg_data.license = license;
             },
-            ComponentChanged(comp) => {
-                self.model.header.emit(HeaderMsg::FileModified);
+            UseComponent(component) => {
+                self.cmb_component.set_active_id(Some(&component));
+            },
+            ComponentChanged(Some(comp)) => if comp != self.model.pkg_data.component {
+                self.record_edit(None);
                 self.model.pkg_data.component = comp;
             },
-            BuildSysChanged(build_sys) => {
-                self.model.header.emit(HeaderMsg::FileModified);
+            BuildSysChanged(Some(build_sys)) => if build_sys != self.model.pkg_data.build_sys {
+                self.record_edit(None);
                 self.model.pkg_data.build_sys = build_sys;
             },
-            SummaryChanged(summary) => {
-                self.model.header.emit(HeaderMsg::FileModified);
+            // Nothing selected, can only happen while the combos are being filled
+            LicenseChanged(None) | ComponentChanged(None) | BuildSysChanged(None) => {},
+            SummaryChanged(summary) => if summary != self.model.pkg_data.summary {
+                self.record_edit(Some("summary"));
                 self.model.pkg_data.summary = summary;
             },
             DescriptionChanged => {
-                self.model.header.emit(HeaderMsg::FileModified);
                 let buffer = self.txt_descr.get_buffer().unwrap();
-                self.model.pkg_data.description = buffer.get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false).unwrap().to_string();
+                let description = buffer.get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false).unwrap().to_string();
+                if description != self.model.pkg_data.description {
+                    self.record_edit(Some("description"));
+                    self.model.pkg_data.description = description;
+                }
+            },
+            SubpkgSelected(Some(name)) => if name != self.model.subpkg {
+                self.model.subpkg = name;
+                self.update_subpkgs();
+            },
+            SubpkgSelected(None) => {},
+            AddSubpkg => {
+                if let Some(name) = ask_for_text(&self.window, "Name of the subpackage (e.g: devel, docs or ^foo for a whole new package)") {
+                    let name = name.trim().to_string();
+                    if !name.is_empty() {
+                        self.model.pkg_data.subpackages.entry(name.clone()).or_default();
+                        self.model.subpkg = name;
+                        self.update_subpkgs();
+                    }
+                }
+            },
+            // Filling the editor from code sends this too, it only counts if something changed
+            SubpkgChanged => {
+                let subpkg = self.read_subpkg();
+                if self.model.pkg_data.subpackages.get(&self.model.subpkg).cloned().unwrap_or_default() != subpkg {
+                    self.record_edit(Some("subpackages"));
+                    self.model.pkg_data.subpackages.insert(self.model.subpkg.clone(), subpkg);
+                }
+            },
+            DescrSourceChanged(origin) => {
+                let descr = self.model.pkg_data.upstream_descriptions.iter()
+                    .find(|(org, _)| Some(org) == origin.as_ref())
+                    .map(|(_, descr)| descr.clone());
+
+                if let Some(descr) = descr {
+                    self.record_edit(None);
+                    self.model.pkg_data.description = descr;
+                    self.update_descr();
+                }
             },
             ShowBuildDeps => {
-                if show_build_deps(&mut self.model.pkg_data.build_deps, &self.window) {
-                    self.model.header.emit(HeaderMsg::FileModified);
+                let mut build_deps = self.model.pkg_data.build_deps.clone();
+                match show_build_deps(&mut build_deps, &self.window) {
+                    Ok(true) => {
+                        self.record_edit(None);
+                        self.model.pkg_data.build_deps = build_deps;
+                    }
+                    Ok(false) => {}
+                    Err(err) => error_dialog(&self.window, "Couldn't translate the build dependencies", &err)
                 }
 
             },
+            ShowPatches => {
+                match self.model.pkg_data.pkg_dir(&self.model.config) {
+                    Some(pkg_dir) => {
+                        let mut patches = self.model.pkg_data.patches.clone();
+                        let mut patch_files = self.model.pkg_data.patch_files.clone();
+                        if patches::show_patches(&self.window, &pkg_dir, &mut patches, &mut patch_files) {
+                            self.record_edit(None);
+                            self.model.pkg_data.patches = patches;
+                            self.model.pkg_data.patch_files = patch_files;
+                        }
+                    }
+                    None => warning_dialog(&self.window, "The package needs a name first, patches go in its folder")
+                }
+            },
             New => {
-                self.model.header.emit(HeaderMsg::FileModified);
-                self.model.pkg_data = PkgData::new();
-                self.update_txt_and_combos();
+                if !self.model.saved && self.model.history.can_undo() && !confirm_dialog(&self.window, "This package has unsaved changes, start a new one anyway?") {
+                    return;
+                }
+                self.set_new();
             }
+            Undo => {
+                let current = self.model.pkg_data.clone();
+                if let Some(pkg_data) = self.model.history.undo(current) {
+                    self.model.pkg_data = self.restored(pkg_data);
+                    self.refresh_form();
+                    self.set_saved(false);
+                }
+            },
+            Redo => {
+                let current = self.model.pkg_data.clone();
+                if let Some(pkg_data) = self.model.history.redo(current) {
+                    self.model.pkg_data = self.restored(pkg_data);
+                    self.refresh_form();
+                    self.set_saved(false);
+                }
+            },
+            KeyPressed(key, state) => {
+                if state.contains(gdk::ModifierType::CONTROL_MASK) && !self.model.busy {
+                    if key == gdk::enums::key::Z || (key == gdk::enums::key::z && state.contains(gdk::ModifierType::SHIFT_MASK)) {
+                        self.update(Redo);
+                    }
+                    else if key == gdk::enums::key::z {
+                        self.update(Undo);
+                    }
+                }
+            },
             LoadFile => {
                 if let Some(pkg_path) = ask_for_file(&self.window) {
-                    let pkg_str = std::fs::read_to_string(&pkg_path).expect("Something went wrong reading package.yml");
-                    let pkg_yaml: YamlPkg = serde_yaml::from_str(&pkg_str).expect("Something went wrong parsing package.yml");
-                    let mut pkg_data: PkgData = pkg_yaml.into();
-                    pkg_data.release += 1; // Update release
-                    pkg_data.file_path = Some(pkg_path);
-                    self.model.pkg_data = pkg_data;
-
-                    self.update_txt_and_combos();
-
-                    self.model.header.emit(HeaderMsg::FileSaved);
+                    match load_pkg(&pkg_path) {
+                        Ok(pkg_data) => self.set_loaded(pkg_data),
+                        Err(err) => error_dialog(&self.window, &format!("Couldn't load {}", pkg_path.display()), &err)
+                    }
                 }
             },
             FromUrl => {
-                if let Some(url_str) = ask_for_url(&self.window) {
-                    let url_data = from_url(&url_str);
-                    self.model.pkg_data.join_url_data(url_data);
-
-                    // Update Gui
-                    self.ent_name.set_text(&self.model.pkg_data.name);
-                    self.ent_version.set_text(&self.model.pkg_data.version);
-                    self.ent_source.set_text(&self.model.pkg_data.source);
-                    self.ent_summary.set_text(&self.model.pkg_data.summary);
-                    self.update_txt_and_combos();
+                if let Some(url_str) = ask_for_text(&self.window, "Please enter the desired URL to analyze") {
+                    let old_version = Some(self.model.pkg_data.version.clone());
+                    let config = self.model.config.clone();
+                    let job = worker::spawn(self.model.sender.clone(), WorkProgress, move |job| from_url(&url_str, old_version, &config, job), UrlAnalyzed);
+                    self.start_work(job, "Analyzing URL");
+                    self.model.busy = true;
+                }
+            },
+            UrlAnalyzed(res) => {
+                self.model.job = None;
+                self.model.busy = false;
+                self.model.header.emit(HeaderMsg::Busy(false));
+                match res {
+                    Ok(url_data) => self.on_url_analyzed(url_data),
+                    Err(Error::Cancelled) => {}
+                    Err(err) => error_dialog(&self.window, "Couldn't analyze the URL", &err)
                 }
             },
             StartMaking => {
-                if self.model.can_start {
-                    let yaml: YamlPkg = self.model.pkg_data.clone().into();
-                    let file_path = {
-                        if let Some(file_path) = &self.model.pkg_data.file_path {
-                            file_path.clone()
+                self.model.save_as = None;
+                if self.start_saving() {
+                    self.model.busy = true;
+                }
+            },
+            SaveAs => {
+                if !self.model.can_start {
+                    cant_start_dialog(&self.window, &self.model.diagnostics);
+                }
+                else if let Some(file_path) = ask_for_save_path(&self.window, &self.model.pkg_data, &self.model.config) {
+                    self.model.save_as = Some(file_path);
+                    if self.start_saving() {
+                        self.model.busy = true;
+                    }
+                }
+            },
+            ShaCalculated(res) => {
+                self.model.job = None;
+                self.model.busy = false;
+                self.model.header.emit(HeaderMsg::Busy(false));
+                let target = self.model.save_as.take();
+                let rendered = res.and_then(|mut check| {
+                    // A source that doesn't check out isn't kept for the next time
+                    if check.report.is_fatal() {
+                        *self.model.pkg_data.tarball_data.borrow_mut() = None;
+                    }
+                    if !check.accepted && check.report.has_concerns() && !integrity_dialog(&self.window, &check.report) {
+                        return Err(Error::Cancelled);
+                    }
+                    // Git sources are asked for their last commit every time
+                    check.accepted = true;
+                    let is_file = matches!(url_kind_analyze(&check.source), Ok(UrlKind::File(_)));
+                    if is_file && !check.report.is_fatal() && check.source == self.model.pkg_data.source {
+                        self.model.pkg_data.source_check = Some(check.clone());
+                    }
+                    render_pkg(&self.model.pkg_data, check.sha)
+                });
+                let saved = rendered.and_then(|text| {
+                    let org_text = self.model.pkg_data.file_text.clone().unwrap_or_default();
+                    let old_name = self.model.pkg_data.file_path.as_ref().map_or("/dev/null".to_string(), |path| path.display().to_string());
+                    let new_name = target.as_ref().map_or(old_name.clone(), |path| path.display().to_string());
+                    let go_on = diff::unified(&org_text, &text, &old_name, &new_name).map_or(true, |diff| review_dialog(&self.window, &diff));
+
+                    if go_on {
+                        save_pkg(&self.model.pkg_data, &text, target, &self.model.config).map(|file_path| Some((file_path, text)))
+                    }
+                    else {
+                        Ok(None)
+                    }
+                });
+
+                match saved {
+                    Ok(Some((file_path, text))) => {
+                        self.model.pkg_data.file_path = Some(file_path);
+                        self.model.pkg_data.file_text = Some(text);
+                        self.model.pkg_data.patch_files = PendingFiles::default();
+                        self.set_saved(true);
+                    }
+                    // The user didn't like what was going to be saved
+                    Ok(None) => {}
+                    Err(Error::Cancelled) => {}
+                    Err(err) => error_dialog(&self.window, "Couldn't save the package", &err)
+                }
+            },
+            OpenRepo => {
+                let chooser = gtk::FileChooserDialog::with_buttons::<gtk::Window>(Some("Open a packaging repository"), Some(&self.window), gtk::FileChooserAction::SelectFolder, &[("Open", gtk::ResponseType::Ok)]);
+                let response = chooser.run();
+                let folder = chooser.get_filename();
+                chooser.destroy();
+
+                if let (gtk::ResponseType::Ok, Some(folder)) = (response, folder) {
+                    let _ = self.model.sender.send(OpenWorkspace(folder));
+                }
+            },
+            OpenWorkspace(root) => {
+                let job = worker::spawn(self.model.sender.clone(), WorkProgress, move |job| workspace::scan(&root, job).map(|entries| (root, entries)), WorkspaceScanned);
+                self.start_work(job, "Looking for packages");
+                self.model.busy = true;
+            },
+            WorkspaceScanned(res) => {
+                self.model.job = None;
+                self.model.busy = false;
+                self.model.header.emit(HeaderMsg::Busy(false));
+                match res {
+                    Ok((root, entries)) => {
+                        self.model.workspace = entries;
+                        self.model.workspace_root = Some(root);
+                        let filter = self.ent_filter.get_text().map_or(String::new(), |text| text.to_string());
+                        self.fill_workspace(&filter);
+                    }
+                    Err(Error::Cancelled) => {},
+                    Err(err) => error_dialog(&self.window, "Couldn't open the repository", &err)
+                }
+            },
+            FilterChanged(filter) => self.fill_workspace(&filter),
+            // A new tab unless it's open already or the current one is empty
+            OpenPackage(row) => {
+                let entry = self.model.shown.get(row as usize).map(|i| self.model.workspace[*i].clone());
+                if let Some(entry) = entry {
+                    if let Some(index) = self.tab_with_file(&entry.path) {
+                        self.switch_tab(index);
+                    }
+                    else {
+                        match load_pkg(&entry.path) {
+                            Ok(pkg_data) => {
+                                let untouched = self.model.pkg_data.file_path.is_none() && !self.model.history.can_undo();
+                                if untouched {
+                                    self.set_loaded(pkg_data);
+                                }
+                                // Filled before it's shown, so the form is refreshed just once
+                                else {
+                                    self.warn_behind_pspec(&pkg_data);
+                                    let index = self.add_tab(TabState::new(pkg_data, true));
+                                    self.switch_tab(index);
+                                }
+                            }
+                            Err(err) => error_dialog(&self.window, &format!("Couldn't load {}", entry.path.display()), &err)
                         }
-                        else {
-                            let pkg_path = Path::new(&std::env::current_dir().unwrap()).join(self.model.pkg_data.name.clone()).to_path_buf();
-                            if !pkg_path.is_dir() {
-                                std::fs::create_dir_all(&pkg_path).unwrap();
+                    }
+                }
+            },
+            TabSwitched(page) => self.switch_tab(page as usize),
+            CloseTab(id) => self.close_tab(id),
+            Commit => {
+                match (self.model.pkg_data.file_path.clone(), self.model.saved) {
+                    (Some(file_path), true) => self.commit(&file_path),
+                    _ => warning_dialog(&self.window, "Save the package first, what's committed is what's on disk")
+                }
+            },
+            // Not part of package.yml, so it isn't an edit of the file
+            EditCves => {
+                if let Some(cves) = cves_dialog(&self.window, &self.model.pkg_data.cves) {
+                    self.model.pkg_data.cves = cves;
+                }
+            },
+            Build => {
+                match (&self.model.pkg_data.file_path, self.model.saved) {
+                    (Some(file_path), true) => {
+                        match build::find_tool(&self.model.config) {
+                            Ok(tool) => {
+                                let file_path = file_path.clone();
+                                let log_sender = self.model.sender.clone();
+                                let log = move |line| {let _ = log_sender.send(BuildLog(line));};
+                                let job = worker::spawn(self.model.sender.clone(), WorkProgress, move |job| build::run(tool, &file_path, log, job), BuildFinished);
+                                self.log_buffer().set_text("");
+                                self.exp_log.set_expanded(true);
+                                self.start_work(job, &format!("Building with {}", tool.name()));
+                                self.model.busy = true;
                             }
-
-                            pkg_path.join("package.yml")
+                            Err(err) => error_dialog(&self.window, "Can't build", &err)
                         }
-                    };
-                    serde_yaml::to_writer(std::fs::File::create(file_path).unwrap(),&yaml).unwrap();
-                    self.model.header.emit(HeaderMsg::FileSaved);
+                    }
+                    _ => warning_dialog(&self.window, "Save the package first, what's built is the package.yml on disk")
                 }
-                else {
-                    cant_start_dialog(&self.window);
+            },
+            BuildLog(line) => self.append_log(&line),
+            BuildFinished(res) => {
+                self.model.job = None;
+                self.model.busy = false;
+                self.model.header.emit(HeaderMsg::Busy(false));
+                match res {
+                    Ok(report) => {
+                        self.show_build_report(&report);
+                        if !report.success {
+                            self.suggest_missing_deps();
+                        }
+                    }
+                    Err(Error::Cancelled) => self.append_log("Build cancelled"),
+                    Err(err) => error_dialog(&self.window, "Couldn't run the build", &err)
+                }
+            },
+            ComponentsLoaded(components) => {
+                self.model.components = components;
+                self.fill_components();
+            },
+            WorkProgress(text, fraction) => self.show_progress(&text, fraction),
+            CancelWork => {
+                // The worker notices and answers with Error::Cancelled
+                if let Some(job) = &self.model.job {
+                    job.cancel();
+                    self.show_progress("Cancelling", None);
                 }
             }
         }
-        self.model.can_start = self.model.pkg_data.is_filled();
+        if pkg_changes {
+            self.model.diagnostics = lint::lint_pkg(&self.model.pkg_data, &self.model.components);
+            self.model.can_start = !lint::has_errors(&self.model.diagnostics);
+            self.show_diagnostics();
+            self.update_component_suggestions();
+        }
     }
 
     fn subscriptions(&mut self, relm: &Relm<Self>) {
         let header = &self.model.header;
         connect!(header@BtnNew, relm, New);
         connect!(header@Load, relm, LoadFile);
+        connect!(header@BtnOpenRepo, relm, OpenRepo);
         connect!(header@BtnFromUrl, relm, FromUrl);
         connect!(header@BtnBuildDeps, relm, ShowBuildDeps);
+        connect!(header@BtnPatches, relm, ShowPatches);
+        connect!(header@BtnBuild, relm, Build);
+        connect!(header@BtnCommit, relm, Commit);
+        connect!(header@BtnSecurity, relm, EditCves);
         connect!(header@BtnSave, relm, StartMaking);
+        connect!(header@BtnSaveAs, relm, SaveAs);
 
         let buffer = &self.txt_descr.get_buffer().unwrap();
         connect!(relm, buffer, connect_changed(_), DescriptionChanged);
+        for view in self.subpkg_views().iter() {
+            let buffer = &view.get_buffer().unwrap();
+            connect!(relm, buffer, connect_changed(_), SubpkgChanged);
+        }
     }
 
     view! {
@@ -831,158 +2257,427 @@ impl Widget for Win {
         gtk::Window {
             titlebar: Some(self.model.header.widget()),
 
-            #[name="app"]
-            gtk::Grid {
-                gtk::Label {
-                    markup: "<b>Name</b>",
-                },
-                #[name="ent_name"]
-                gtk::Entry {
-                    text: &self.model.pkg_data.name,
-                    changed(entry) => NameChanged(entry.get_text().expect("get_text failed").to_string()),
-                    
-                    hexpand: true,
-                    cell : {
-                        width: RIGHT_COL_PROPORTION
-                    }
-                },
-                gtk::Label {
-                    markup: "<b>Version</b>",
-                    cell: {
-                        top_attach: 1,
-                        left_attach: 0,
-                        width: 1
-                    }
-                },
-                #[name="ent_version"]
-                gtk::Entry {
-                    text: &self.model.pkg_data.version,
-                    changed(entry) => VersionChanged(entry.get_text().expect("get_text failed").to_string()),
-
-                    hexpand: true,
-                    cell: {
-                        top_attach: 1,
-                        left_attach: 1,
-                        width: RIGHT_COL_PROPORTION
-                    }
-                },
-                gtk::Label {
-                    markup: "<b>URL</b>",
-                    cell: {
-                        top_attach: 2,
-                        left_attach: 0,
-                    }
-                },
-                #[name="ent_source"]
-                gtk::Entry {
-                    text: &self.model.pkg_data.source,
-                    changed(entry) => UrlChanged(entry.get_text().expect("get_text failed").to_string()),                    
-                    
-                    hexpand: true,
-                    cell: {
-                        top_attach: 2,
-                        left_attach: 1,
-                        width: RIGHT_COL_PROPORTION
-                    }
-                },
-                gtk::Label {
-                    markup: "<b>License</b>",
-                    cell: {
-                        top_attach: 3,
-                        left_attach: 0
-                    }
-                },
-                #[name="cmb_license"]
-                gtk::ComboBoxText {
-                    changed(combo) => LicenseChanged(combo.get_active_text().expect("get_active_text failed").to_string()),
-                    
-                    hexpand: true,
-                    cell: {
-                        top_attach: 3,
-                        left_attach: 1,
-                        width: RIGHT_COL_PROPORTION
-                    },
-                },
-                gtk::Label {
-                    markup: "<b>Component</b>",
-                    cell: {
-                        top_attach: 4,
-                        left_attach: 0
-                    }
-                },
-                #[name="cmb_component"]
-                gtk::ComboBoxText {
-                    changed(combo) => ComponentChanged(combo.get_active_text().expect("get_active_text failed").to_string()),
-
-                    hexpand: true,
-                    cell: {
-                        top_attach: 4,
-                        left_attach: 1,
-                        width: RIGHT_COL_PROPORTION
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+
+                gtk::Paned {
+                    orientation: gtk::Orientation::Horizontal,
+                    vexpand: true,
+
+                    // The open repository
+                    gtk::Box {
+                        orientation: gtk::Orientation::Vertical,
+                        spacing: 6,
+                        visible: self.model.workspace_root.is_some(),
+                        sensitive: !self.model.busy,
+                        property_width_request: 250,
+
+                        #[name="ent_filter"]
+                        gtk::SearchEntry {
+                            search_changed(entry) => FilterChanged(entry.get_text().map_or(String::new(), |text| text.to_string())),
+                            placeholder_text: Some("Name or component")
+                        },
+                        gtk::ScrolledWindow {
+                            vexpand: true,
+
+                            #[name="lst_packages"]
+                            gtk::ListBox {
+                                row_activated(_, row) => OpenPackage(row.get_index())
+                            }
+                        }
                     },
-                },
-                gtk::Label {
-                    markup: "<b>Build Sys</b>",
-                    cell: {
-                        top_attach: 5,
-                        left_attach:0
-                    }
-                },
-                #[name="cmb_buildsys"]
-                gtk::ComboBoxText {
-                    changed(combo) => BuildSysChanged(combo.get_active_text().expect("get_active_text failed").to_string()),
-
-                    hexpand: true,
-                    cell: {
-                        top_attach: 5,
-                        left_attach: 1,
-                        width: RIGHT_COL_PROPORTION
-                    }
-                },
-                gtk::Label {
-                    markup: "<b>Summary</b>",
-                    cell: {
-                        top_attach: 6,
-                        left_attach: 0
+                    gtk::Box {
+                        orientation: gtk::Orientation::Vertical,
+
+                        // Pages are empty, they only pick which package is in the editor
+                        #[name="tabs"]
+                        gtk::Notebook {
+                            scrollable: true,
+                            show_border: false,
+                            sensitive: !self.model.busy,
+                            switch_page(_, _, page) => TabSwitched(page)
+                        },
+                        #[name="app"]
+                        gtk::Grid {
+                            // Nothing can change while the background work uses it
+                            sensitive: !self.model.busy,
+                            child: {
+                                expand: true
+                            },
+                            gtk::Label {
+                                markup: "<b>Name</b>",
+                            },
+                            #[name="ent_name"]
+                            gtk::Entry {
+                                text: &self.model.pkg_data.name,
+                                changed(entry) => NameChanged(entry.get_text().expect("get_text failed").to_string()),
+                            
+                                hexpand: true,
+                                cell : {
+                                    width: RIGHT_COL_PROPORTION
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>Version</b>",
+                                cell: {
+                                    top_attach: 1,
+                                    left_attach: 0,
+                                    width: 1
+                                }
+                            },
+                            #[name="ent_version"]
+                            gtk::Entry {
+                                text: &self.model.pkg_data.version,
+                                changed(entry) => VersionChanged(entry.get_text().expect("get_text failed").to_string()),
+
+                                hexpand: true,
+                                cell: {
+                                    top_attach: 1,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION - 3
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>Release</b>",
+                                cell: {
+                                    top_attach: 1,
+                                    left_attach: RIGHT_COL_PROPORTION - 2
+                                }
+                            },
+                            #[name="spin_release"]
+                            gtk::SpinButton {
+                                adjustment: &gtk::Adjustment::new(1.0, 1.0, u16::max_value() as f64, 1.0, 10.0, 0.0),
+                                value_changed(spin) => ReleaseChanged(spin.get_value_as_int() as u16),
+                                cell: {
+                                    top_attach: 1,
+                                    left_attach: RIGHT_COL_PROPORTION - 1
+                                }
+                            },
+                            gtk::Button {
+                                clicked => BumpRelease,
+                                label: "Bump",
+                                tooltip_text: Some("Go up one release"),
+                                cell: {
+                                    top_attach: 1,
+                                    left_attach: RIGHT_COL_PROPORTION
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>URL</b>",
+                                cell: {
+                                    top_attach: 2,
+                                    left_attach: 0,
+                                }
+                            },
+                            #[name="ent_source"]
+                            gtk::Entry {
+                                text: &self.model.pkg_data.source,
+                                changed(entry) => UrlChanged(entry.get_text().expect("get_text failed").to_string()),                    
+                            
+                                hexpand: true,
+                                cell: {
+                                    top_attach: 2,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>License</b>",
+                                cell: {
+                                    top_attach: 3,
+                                    left_attach: 0
+                                }
+                            },
+                            #[name="cmb_license"]
+                            gtk::ComboBoxText {
+                                changed(combo) => LicenseChanged(combo.get_active_text().map(|text| text.to_string())),
+                            
+                                hexpand: true,
+                                cell: {
+                                    top_attach: 3,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION
+                                },
+                            },
+                            gtk::Label {
+                                markup: "<b>Component</b>",
+                                cell: {
+                                    top_attach: 4,
+                                    left_attach: 0
+                                }
+                            },
+                            #[name="cmb_component"]
+                            gtk::ComboBoxText {
+                                // The text has the description too
+                                changed(combo) => ComponentChanged(combo.get_active_id().map(|id| id.to_string())),
+
+                                hexpand: true,
+                                cell: {
+                                    top_attach: 4,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION - 2
+                                },
+                            },
+                            // Filled by update_component_suggestions
+                            #[name="box_suggestions"]
+                            gtk::Box {
+                                spacing: 6,
+                                tooltip_text: Some("Components that seem to fit the package"),
+                                cell: {
+                                    top_attach: 4,
+                                    left_attach: RIGHT_COL_PROPORTION - 1,
+                                    width: 2
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>Build Sys</b>",
+                                cell: {
+                                    top_attach: 5,
+                                    left_attach:0
+                                }
+                            },
+                            #[name="cmb_buildsys"]
+                            gtk::ComboBoxText {
+                                changed(combo) => BuildSysChanged(combo.get_active_text().map(|text| text.to_string())),
+
+                                hexpand: true,
+                                cell: {
+                                    top_attach: 5,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>Summary</b>",
+                                cell: {
+                                    top_attach: 6,
+                                    left_attach: 0
+                                }
+                            },
+                            #[name="ent_summary"]
+                            gtk::Entry { // Summary
+                                text: &self.model.pkg_data.summary,
+                                changed(entry) => SummaryChanged(entry.get_text().expect("get_text failed").to_string()),
+
+                                hexpand: true,
+                                cell: {
+                                    top_attach: 6,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>Description</b>",
+                                cell: {
+                                    top_attach: 7,
+                                    left_attach: 0
+                                }
+                            },
+                            #[name="cmb_descr_source"]
+                            gtk::ComboBoxText {
+                                changed(combo) => DescrSourceChanged(combo.get_active_id().map(|id| id.to_string())),
+                                tooltip_text: Some("Use a description found upstream"),
+
+                                valign: gtk::Align::Start,
+                                cell: {
+                                    top_attach: 8,
+                                    left_attach: 0
+                                }
+                            },
+                            #[name="txt_descr"]
+                            gtk::TextView {
+                                wrap_mode: gtk::WrapMode::Word,
+
+                                hexpand: true,
+                                vexpand: true,
+                                cell: {
+                                    top_attach: 7,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION,
+                                    height: 2
+                                }
+                            },
+                            gtk::Label {
+                                markup: "<b>Subpackage</b>",
+                                cell: {
+                                    top_attach: 9,
+                                    left_attach: 0
+                                }
+                            },
+                            #[name="cmb_subpkg"]
+                            gtk::ComboBoxText {
+                                changed(combo) => SubpkgSelected(combo.get_active_id().map(|id| id.to_string())),
+                                hexpand: true,
+                                cell: {
+                                    top_attach: 9,
+                                    left_attach: 1,
+                                    width: RIGHT_COL_PROPORTION - 1
+                                }
+                            },
+                            gtk::Button {
+                                clicked => AddSubpkg,
+                                label: "Add",
+                                tooltip_text: Some("Add a subpackage"),
+                                cell: {
+                                    top_attach: 9,
+                                    left_attach: RIGHT_COL_PROPORTION
+                                }
+                            },
+                            gtk::Label {
+                                text: "Patterns",
+                                halign: gtk::Align::Start,
+                                cell: {
+                                    top_attach: 10,
+                                    left_attach: 1,
+                                    width: 4
+                                }
+                            },
+                            gtk::Label {
+                                text: "Run deps",
+                                halign: gtk::Align::Start,
+                                cell: {
+                                    top_attach: 10,
+                                    left_attach: 5,
+                                    width: 3
+                                }
+                            },
+                            gtk::Label {
+                                text: "Replaces",
+                                halign: gtk::Align::Start,
+                                cell: {
+                                    top_attach: 10,
+                                    left_attach: 8,
+                                    width: 3
+                                }
+                            },
+                            #[name="txt_patterns"]
+                            gtk::TextView {
+                                hexpand: true,
+                                property_height_request: 80,
+                                cell: {
+                                    top_attach: 11,
+                                    left_attach: 1,
+                                    width: 4
+                                }
+                            },
+                            #[name="txt_rundeps"]
+                            gtk::TextView {
+                                hexpand: true,
+                                property_height_request: 80,
+                                cell: {
+                                    top_attach: 11,
+                                    left_attach: 5,
+                                    width: 3
+                                }
+                            },
+                            #[name="txt_replaces"]
+                            gtk::TextView {
+                                hexpand: true,
+                                property_height_request: 80,
+                                cell: {
+                                    top_attach: 11,
+                                    left_attach: 8,
+                                    width: 3
+                                }
+                            }
+                        }
                     }
                 },
-                #[name="ent_summary"]
-                gtk::Entry { // Summary
-                    text: &self.model.pkg_data.summary,
-                    changed(entry) => SummaryChanged(entry.get_text().expect("get_text failed").to_string()),
-
-                    hexpand: true,
-                    cell: {
-                        top_attach: 6,
-                        left_attach: 1,
-                        width: RIGHT_COL_PROPORTION
+                #[name="exp_changes"]
+                gtk::Expander {
+                    label: Some("Upstream changes"),
+                    visible: false,
+
+                    gtk::ScrolledWindow {
+                        property_height_request: 200,
+
+                        #[name="txt_changes"]
+                        gtk::TextView {
+                            editable: false,
+                            wrap_mode: gtk::WrapMode::Word
+                        }
                     }
                 },
-                gtk::Label {
-                    markup: "<b>Description</b>",
-                    cell: {
-                        top_attach: 7,
-                        left_attach: 0
+                #[name="exp_log"]
+                gtk::Expander {
+                    label: Some("Build log"),
+
+                    gtk::ScrolledWindow {
+                        property_height_request: 200,
+
+                        #[name="txt_log"]
+                        gtk::TextView {
+                            editable: false,
+                            property_monospace: true,
+                            wrap_mode: gtk::WrapMode::Char
+                        }
                     }
                 },
-                #[name="txt_descr"]
-                gtk::TextView {
-                    wrap_mode: gtk::WrapMode::Word,
-
-                    hexpand: true,
-                    vexpand: true,
-                    cell: {
-                        top_attach: 7,
-                        left_attach: 1,
-                        width: RIGHT_COL_PROPORTION
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    spacing: 6,
+                    visible: self.model.busy,
+
+                    #[name="progress"]
+                    gtk::ProgressBar {
+                        show_text: true,
+                        hexpand: true,
+                        valign: gtk::Align::Center
+                    },
+                    gtk::Button {
+                        clicked => CancelWork,
+                        label: "Cancel"
                     }
                 }
             },
+            key_press_event(_, key) => (KeyPressed(key.get_keyval(), key.get_state()), Inhibit(false)),
             delete_event(_, _) => (Quit, Inhibit(false)),
         }
     }
 }
 
+// packmak lint [package.yml...], returns the exit code
+fn lint_cli(paths: &[String]) -> i32 {
+    let default = ["package.yml".to_string()];
+    let paths = if paths.is_empty() {&default[..]} else {paths};
+    // A broken config is only a warning, the defaults still lint
+    let config = Config::load().unwrap_or_else(|err| {
+        println!("{} {}", console::style("warning").yellow().bold(), err);
+        Config::default()
+    });
+    let components = components::load(&config);
+    let mut code = 0;
+
+    for path in paths {
+        let diags = std::fs::read_to_string(path).map_err(Error::from).and_then(|text| lint::lint_file(&text, &components));
+        match diags {
+            Ok(diags) => {
+                for diag in diags.iter() {
+                    let severity = match diag.severity {
+                        Severity::Error => console::style("error").red().bold(),
+                        Severity::Warning => console::style("warning").yellow().bold()
+                    };
+                    println!("{}: {} [{}] {}", path, severity, diag.rule, diag.message);
+                }
+                if lint::has_errors(&diags) {
+                    code = 1;
+                }
+            }
+            Err(err) => {
+                println!("{}: {} {}", path, console::style("error").red().bold(), err);
+                code = 1;
+            }
+        }
+    }
+
+    code
+}
+
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("lint") {
+        std::process::exit(lint_cli(&args[1..]));
+    }
+
     Win::run(()).expect("Window::run");
 }
@@ -0,0 +1,596 @@
+use std::fmt;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::config::Config;
+use crate::description;
+
+// Whatever the upstream hosting knows about a project
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamMeta {
+	pub summary: Option<String>,
+	pub description: Option<String>,
+	pub homepage: Option<String>,
+	pub license: Option<String>,
+	pub latest_release: Option<String>,
+	// Tags the project was given on its forge
+	pub topics: Vec<String>
+}
+
+#[derive(Debug)]
+pub enum MetadataError {
+	Network(reqwest::Error),
+	// The API answered but with something we couldn't understand
+	Parse(reqwest::Error),
+	NotFound,
+	RateLimited(Option<DateTime<Utc>>),
+	Status(StatusCode),
+	// The URL doesn't say which project it belongs to
+	UnknownProject
+}
+
+impl fmt::Display for MetadataError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MetadataError::Network(err) => write!(f, "couldn't reach the API: {}", err),
+			MetadataError::Parse(err) => write!(f, "unexpected answer from the API: {}", err),
+			MetadataError::NotFound => write!(f, "the project wasn't found"),
+			MetadataError::RateLimited(Some(reset)) => write!(f, "API rate limit exceeded, try again after {} (setting a token raises the limit)", reset.with_timezone(&Local).format("%H:%M")),
+			MetadataError::RateLimited(None) => write!(f, "API rate limit exceeded, try again later (setting a token raises the limit)"),
+			MetadataError::Status(status) => write!(f, "the API answered {}", status),
+			MetadataError::UnknownProject => write!(f, "can't tell the project from the URL")
+		}
+	}
+}
+
+impl std::error::Error for MetadataError {}
+
+pub trait MetadataProvider {
+	// Whether this provider understands the host of url
+	fn handles(&self, url: &Url) -> bool;
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError>;
+}
+
+const USER_AGENT: &str = concat!("packmak/", env!("CARGO_PKG_VERSION"));
+
+fn check_status(resp: reqwest::blocking::Response) -> Result<reqwest::blocking::Response, MetadataError> {
+	let status = resp.status();
+	let header_num = |name: &str| resp.headers().get(name).and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<i64>().ok());
+
+	if status.is_success() {
+		Ok(resp)
+	}
+	else if status == StatusCode::NOT_FOUND {
+		Err(MetadataError::NotFound)
+	}
+	// GitHub answers 403 with no remaining calls, others use 429
+	else if status == StatusCode::TOO_MANY_REQUESTS || (status == StatusCode::FORBIDDEN && header_num("x-ratelimit-remaining") == Some(0)) {
+		let reset = header_num("x-ratelimit-reset").and_then(|secs| Utc.timestamp_opt(secs, 0).single());
+		Err(MetadataError::RateLimited(reset))
+	}
+	else {
+		Err(MetadataError::Status(status))
+	}
+}
+
+fn get_json_with<T: DeserializeOwned>(api_url: &str, token: Option<&str>) -> Result<T, MetadataError> {
+	let client = reqwest::blocking::Client::builder().user_agent(USER_AGENT).build().map_err(MetadataError::Network)?;
+	let mut req = client.get(api_url);
+	if let Some(token) = token {
+		req = req.header("Authorization", format!("token {}", token));
+	}
+
+	let resp = check_status(req.send().map_err(MetadataError::Network)?)?;
+	resp.json().map_err(MetadataError::Parse)
+}
+
+fn get_json<T: DeserializeOwned>(api_url: &str) -> Result<T, MetadataError> {
+	get_json_with(api_url, None)
+}
+
+// Make license ids from APIs look like the ones Solus uses
+pub fn spdx_id(id: &str) -> String {
+	match id {
+		"GPL-3.0" => "GPL-3.0-or-later".to_string(),
+		"GPL-2.0" => "GPL-2.0-or-later".to_string(),
+		"LGPL-2.1" => "LGPL-2.1-or-later".to_string(),
+		"LGPL-3.0" => "LGPL-3.0-or-later".to_string(),
+		_ => id.to_string()
+	}
+}
+
+fn non_empty(text: Option<String>) -> Option<String> {
+	text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+// First two path segments, like author/repo, without any .git
+fn owner_and_repo(url: &Url) -> Option<(String, String)> {
+	let mut segments = url.path_segments()?;
+	let owner = segments.next().filter(|s| !s.is_empty())?;
+	let repo = segments.next().filter(|s| !s.is_empty())?;
+	Some((owner.to_string(), repo.trim_end_matches(".git").to_string()))
+}
+
+// The first release tag of an API that lists releases newest first
+#[derive(Deserialize)]
+struct ApiRelease {
+	tag_name: Option<String>
+}
+
+// token is the one in the config, if any, for the higher rate limit
+pub struct GitHub {
+	pub token: Option<String>
+}
+
+#[derive(Deserialize)]
+struct RepoApiLicense {
+	spdx_id: Option<String>
+}
+
+// Anything here can be null, e.g: repos without a detected license
+#[derive(Deserialize)]
+struct RepoApiCall {
+	description: Option<String>,
+	homepage: Option<String>,
+	html_url: Option<String>,
+	license: Option<RepoApiLicense>,
+	#[serde(default)]
+	topics: Vec<String>
+}
+
+fn is_github(url: &Url) -> bool {
+	url.host_str() == Some("github.com")
+}
+
+fn ask_gh_api_repo(author: &str, repo: &str, token: Option<&str>) -> Result<RepoApiCall, MetadataError> {
+	get_json_with(&format!("https://api.github.com/repos/{}/{}", author, repo), token)
+}
+
+impl MetadataProvider for GitHub {
+	fn handles(&self, url: &Url) -> bool {
+		is_github(url)
+	}
+
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError> {
+		let (author, repo) = owner_and_repo(url).ok_or(MetadataError::UnknownProject)?;
+		let token = self.token.as_deref();
+		let resp = ask_gh_api_repo(&author, &repo, token)?;
+		// Not every repo has releases, that's fine
+		let release: Option<ApiRelease> = get_json_with(&format!("https://api.github.com/repos/{}/{}/releases/latest", author, repo), token).ok();
+
+		Ok(UpstreamMeta {
+			summary: non_empty(resp.description),
+			description: None,
+			homepage: non_empty(resp.homepage).or(resp.html_url),
+			// GitHub says NOASSERTION when there's a license it doesn't recognize
+			license: resp.license.and_then(|l| l.spdx_id).filter(|id| id != "NOASSERTION").map(|id| spdx_id(&id)),
+			latest_release: release.and_then(|r| r.tag_name),
+			topics: resp.topics
+		})
+	}
+}
+
+pub struct GitLab;
+
+// Projects can be nested in groups, the path ends where GitLab's own routes start
+fn gitlab_api_url(url: &Url) -> Result<String, MetadataError> {
+	let host = url.host_str().ok_or(MetadataError::UnknownProject)?;
+	let path = url.path_segments().ok_or(MetadataError::UnknownProject)?
+		.take_while(|segment| *segment != "-" && !segment.is_empty())
+		.map(|segment| segment.trim_end_matches(".git"))
+		.collect::<Vec<_>>();
+	if path.len() < 2 {
+		return Err(MetadataError::UnknownProject);
+	}
+
+	Ok(format!("https://{}/api/v4/projects/{}", host, path.join("%2F")))
+}
+
+const GITLAB_HOSTS: &[&str] = &["gitlab.com", "gitlab.gnome.org", "invent.kde.org", "gitlab.freedesktop.org", "salsa.debian.org", "gitlab.xfce.org"];
+
+#[derive(Deserialize)]
+struct GlLicense {
+	key: Option<String>
+}
+
+#[derive(Deserialize)]
+struct GlProject {
+	description: Option<String>,
+	web_url: Option<String>,
+	license: Option<GlLicense>,
+	#[serde(default)]
+	topics: Vec<String>
+}
+
+// GitLab only gives lowercase keys like gpl-3.0 or mit
+fn license_from_key(key: &str) -> String {
+	match key {
+		"mit" => "MIT".to_string(),
+		"bsd-2-clause" => "BSD-2-Clause".to_string(),
+		"bsd-3-clause" => "BSD-3-Clause".to_string(),
+		"apache-2.0" => "Apache-2.0".to_string(),
+		"mpl-2.0" => "MPL-2.0".to_string(),
+		_ => spdx_id(&key.to_uppercase())
+	}
+}
+
+impl MetadataProvider for GitLab {
+	fn handles(&self, url: &Url) -> bool {
+		url.host_str().map_or(false, |host| GITLAB_HOSTS.contains(&host) || host.starts_with("gitlab."))
+	}
+
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError> {
+		let api_url = gitlab_api_url(url)?;
+		let resp: GlProject = get_json(&format!("{}?license=true", api_url))?;
+		let releases: Option<Vec<ApiRelease>> = get_json(&format!("{}/releases", api_url)).ok();
+
+		Ok(UpstreamMeta {
+			summary: non_empty(resp.description),
+			description: None,
+			homepage: resp.web_url,
+			license: resp.license.and_then(|l| l.key).map(|key| license_from_key(&key)),
+			latest_release: releases.and_then(|r| r.into_iter().next()).and_then(|r| r.tag_name),
+			topics: resp.topics
+		})
+	}
+}
+
+pub struct Gitea;
+
+const GITEA_HOSTS: &[&str] = &["codeberg.org", "gitea.com"];
+
+#[derive(Deserialize)]
+struct GiteaRepo {
+	description: Option<String>,
+	website: Option<String>,
+	html_url: Option<String>,
+	licenses: Option<Vec<String>>,
+	#[serde(default)]
+	topics: Vec<String>
+}
+
+impl MetadataProvider for Gitea {
+	fn handles(&self, url: &Url) -> bool {
+		url.host_str().map_or(false, |host| GITEA_HOSTS.contains(&host) || host.starts_with("gitea."))
+	}
+
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError> {
+		let host = url.host_str().ok_or(MetadataError::UnknownProject)?;
+		let (owner, repo) = owner_and_repo(url).ok_or(MetadataError::UnknownProject)?;
+		let api_url = format!("https://{}/api/v1/repos/{}/{}", host, owner, repo);
+		let resp: GiteaRepo = get_json(&api_url)?;
+		let releases: Option<Vec<ApiRelease>> = get_json(&format!("{}/releases?limit=1", api_url)).ok();
+
+		Ok(UpstreamMeta {
+			summary: non_empty(resp.description),
+			description: None,
+			homepage: non_empty(resp.website).or(resp.html_url),
+			license: resp.licenses.and_then(|l| l.into_iter().next()).map(|id| spdx_id(&id)),
+			latest_release: releases.and_then(|r| r.into_iter().next()).and_then(|r| r.tag_name),
+			topics: resp.topics
+		})
+	}
+}
+
+pub struct SourceForge;
+
+#[derive(Deserialize)]
+struct SfLicense {
+	shortname: Option<String>
+}
+
+#[derive(Deserialize)]
+struct SfCategories {
+	license: Option<Vec<SfLicense>>
+}
+
+#[derive(Deserialize)]
+struct SfProject {
+	short_description: Option<String>,
+	summary: Option<String>,
+	external_homepage: Option<String>,
+	url: Option<String>,
+	categories: Option<SfCategories>
+}
+
+#[derive(Deserialize)]
+struct SfBestRelease {
+	filename: Option<String>
+}
+
+#[derive(Deserialize)]
+struct SfBest {
+	release: Option<SfBestRelease>
+}
+
+// SourceForge license shortnames aren't SPDX
+fn license_from_sf(shortname: &str) -> String {
+	match shortname {
+		"gplv3" => "GPL-3.0-or-later".to_string(),
+		"gplv2" => "GPL-2.0-or-later".to_string(),
+		"lgplv3" => "LGPL-3.0-or-later".to_string(),
+		"lgplv2" => "LGPL-2.1-or-later".to_string(),
+		"mit" => "MIT".to_string(),
+		"bsd" => "BSD-3-Clause".to_string(),
+		"apache2" => "Apache-2.0".to_string(),
+		other => other.to_string()
+	}
+}
+
+impl SourceForge {
+	fn project_name(url: &Url) -> Option<String> {
+		let host = url.host_str()?;
+		let segments = url.path_segments()?.collect::<Vec<_>>();
+		match segments.as_slice() {
+			// sourceforge.net/projects/foo/... and downloads.sourceforge.net/project/foo/...
+			["projects", name, ..] | ["project", name, ..] => Some(name.to_string()),
+			// foo.sourceforge.net and foo.sourceforge.io
+			_ => host.strip_suffix(".sourceforge.net").or_else(|| host.strip_suffix(".sourceforge.io"))
+				.filter(|name| !name.contains('.') && *name != "downloads")
+				.map(|name| name.to_string())
+		}
+	}
+}
+
+impl MetadataProvider for SourceForge {
+	fn handles(&self, url: &Url) -> bool {
+		url.host_str().map_or(false, |host| host.ends_with("sourceforge.net") || host.ends_with("sourceforge.io"))
+	}
+
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError> {
+		let name = Self::project_name(url).ok_or(MetadataError::UnknownProject)?;
+		let resp: SfProject = get_json(&format!("https://sourceforge.net/rest/p/{}", name))?;
+		let best: Option<SfBest> = get_json(&format!("https://sourceforge.net/projects/{}/best_release.json", name)).ok();
+		let latest_file = best.and_then(|b| b.release).and_then(|r| r.filename);
+
+		Ok(UpstreamMeta {
+			summary: non_empty(resp.summary.clone()).or_else(|| non_empty(resp.short_description.clone())),
+			description: non_empty(resp.short_description),
+			homepage: non_empty(resp.external_homepage).or(resp.url),
+			license: resp.categories.and_then(|c| c.license).and_then(|l| l.into_iter().next()).and_then(|l| l.shortname).map(|l| license_from_sf(&l)),
+			// Only the file is known, which is the closest thing to a release here
+			latest_release: latest_file.and_then(|f| f.rsplit('/').next().map(|f| f.to_string())),
+			topics: Vec::new()
+		})
+	}
+}
+
+pub struct PyPi;
+
+#[derive(Deserialize)]
+struct PyPiInfo {
+	summary: Option<String>,
+	description: Option<String>,
+	home_page: Option<String>,
+	project_url: Option<String>,
+	license: Option<String>,
+	version: Option<String>
+}
+
+#[derive(Deserialize)]
+struct PyPiProject {
+	info: PyPiInfo
+}
+
+// Name of a package out of a file like foo_bar-1.2.3.tar.gz
+fn name_from_file(file: &str) -> Option<String> {
+	let end = file.find('-').unwrap_or(file.len());
+	Some(file[..end].to_string()).filter(|name| !name.is_empty())
+}
+
+impl MetadataProvider for PyPi {
+	fn handles(&self, url: &Url) -> bool {
+		url.host_str().map_or(false, |host| host == "pypi.org" || host == "files.pythonhosted.org" || host == "pypi.python.org")
+	}
+
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError> {
+		let segments = url.path_segments().ok_or(MetadataError::UnknownProject)?.collect::<Vec<_>>();
+		let name = match segments.as_slice() {
+			["project", name, ..] | ["pypi", name, ..] => name.to_string(),
+			[.., file] => name_from_file(file).ok_or(MetadataError::UnknownProject)?,
+			_ => return Err(MetadataError::UnknownProject)
+		};
+
+		let resp: PyPiProject = get_json(&format!("https://pypi.org/pypi/{}/json", name))?;
+		Ok(UpstreamMeta {
+			summary: non_empty(resp.info.summary),
+			// The whole README, only its first paragraphs are a description
+			description: resp.info.description.as_deref().and_then(description::from_readme).and_then(|descr| descr.description),
+			homepage: non_empty(resp.info.home_page).or(resp.info.project_url),
+			license: non_empty(resp.info.license),
+			latest_release: resp.info.version,
+			topics: Vec::new()
+		})
+	}
+}
+
+pub struct CratesIo;
+
+#[derive(Deserialize)]
+struct CrateInfo {
+	description: Option<String>,
+	homepage: Option<String>,
+	repository: Option<String>,
+	max_version: Option<String>
+}
+
+#[derive(Deserialize)]
+struct CrateVersion {
+	license: Option<String>
+}
+
+#[derive(Deserialize)]
+struct CrateResp {
+	#[serde(rename = "crate")]
+	krate: CrateInfo,
+	versions: Option<Vec<CrateVersion>>
+}
+
+impl MetadataProvider for CratesIo {
+	fn handles(&self, url: &Url) -> bool {
+		url.host_str().map_or(false, |host| host == "crates.io" || host == "static.crates.io")
+	}
+
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError> {
+		let segments = url.path_segments().ok_or(MetadataError::UnknownProject)?.collect::<Vec<_>>();
+		let name = match segments.as_slice() {
+			// crates.io/crates/foo, static.crates.io/crates/foo/foo-1.0.crate, crates.io/api/v1/crates/foo/1.0/download
+			["crates", name, ..] | ["api", "v1", "crates", name, ..] => name.to_string(),
+			_ => return Err(MetadataError::UnknownProject)
+		};
+
+		let resp: CrateResp = get_json(&format!("https://crates.io/api/v1/crates/{}", name))?;
+		Ok(UpstreamMeta {
+			summary: non_empty(resp.krate.description),
+			description: None,
+			homepage: non_empty(resp.krate.homepage).or(resp.krate.repository),
+			license: resp.versions.and_then(|v| v.into_iter().next()).and_then(|v| v.license),
+			latest_release: resp.krate.max_version,
+			topics: Vec::new()
+		})
+	}
+}
+
+pub struct Npm;
+
+#[derive(Deserialize)]
+struct NpmDistTags {
+	latest: Option<String>
+}
+
+#[derive(Deserialize)]
+struct NpmPackage {
+	description: Option<String>,
+	readme: Option<String>,
+	homepage: Option<String>,
+	license: Option<String>,
+	#[serde(rename = "dist-tags")]
+	dist_tags: Option<NpmDistTags>
+}
+
+impl MetadataProvider for Npm {
+	fn handles(&self, url: &Url) -> bool {
+		url.host_str().map_or(false, |host| host == "registry.npmjs.org" || host == "www.npmjs.com" || host == "npmjs.com")
+	}
+
+	fn fetch(&self, url: &Url) -> Result<UpstreamMeta, MetadataError> {
+		let segments = url.path_segments().ok_or(MetadataError::UnknownProject)?.collect::<Vec<_>>();
+		let name = match segments.as_slice() {
+			// www.npmjs.com/package/foo or registry.npmjs.org/foo/-/foo-1.0.0.tgz, also with @scope/
+			["package", scope, name, ..] if scope.starts_with('@') => format!("{}/{}", scope, name),
+			["package", name, ..] => name.to_string(),
+			[scope, name, "-", ..] if scope.starts_with('@') => format!("{}/{}", scope, name),
+			[name, ..] if !name.is_empty() => name.to_string(),
+			_ => return Err(MetadataError::UnknownProject)
+		};
+
+		let resp: NpmPackage = get_json(&format!("https://registry.npmjs.org/{}", name.replace('/', "%2F")))?;
+		Ok(UpstreamMeta {
+			summary: non_empty(resp.description),
+			description: resp.readme.as_deref().and_then(description::from_readme).and_then(|descr| descr.description),
+			homepage: resp.homepage,
+			license: resp.license,
+			latest_release: resp.dist_tags.and_then(|tags| tags.latest),
+			topics: Vec::new()
+		})
+	}
+}
+
+// The provider that knows about the host of url, if any
+pub fn provider_for(url: &Url, config: &Config) -> Option<Box<dyn MetadataProvider>> {
+	let providers: Vec<Box<dyn MetadataProvider>> = vec![
+		Box::new(GitHub {token: config.github_token()}),
+		Box::new(GitLab),
+		Box::new(Gitea),
+		Box::new(SourceForge),
+		Box::new(PyPi),
+		Box::new(CratesIo),
+		Box::new(Npm)
+	];
+
+	providers.into_iter().find(|provider| provider.handles(url))
+}
+
+// None if no provider knows about this host
+pub fn fetch_for(url: &Url, config: &Config) -> Option<Result<UpstreamMeta, MetadataError>> {
+	provider_for(url, config).map(|provider| provider.fetch(url))
+}
+
+const ARCHIVE_ENDINGS: &[&str] = &[".tar.gz", ".tar.xz", ".tar.bz2", ".tgz", ".zip"];
+
+fn strip_archive_ending(name: &str) -> &str {
+	ARCHIVE_ENDINGS.iter().find(|ending| name.ends_with(*ending)).map_or(name, |ending| &name[..name.len() - ending.len()])
+}
+
+// The release page of the tag a GitHub or GitLab source was made from
+pub fn release_notes_url(url: &Url) -> Option<String> {
+	let host = url.host_str()?;
+	let segments = url.path_segments()?.collect::<Vec<_>>();
+
+	if is_github(url) {
+		// archive/refs/tags/<tag>.tar.gz, archive/<tag>.tar.gz or releases/download/<tag>/<file>
+		let tag = match segments.get(2..)? {
+			["archive", "refs", "tags", file] | ["archive", file] => strip_archive_ending(file),
+			["releases", "download", tag, _] => tag,
+			_ => return None
+		};
+		Some(format!("https://github.com/{}/{}/releases/tag/{}", segments[0], segments[1], tag))
+	}
+	else if GitLab.handles(url) {
+		// <project>/-/archive/<tag>/<file>
+		let dash = segments.iter().position(|segment| *segment == "-")?;
+		match segments.get(dash + 1..)? {
+			["archive", tag, _] => Some(format!("https://{}/{}/-/releases/{}", host, segments[..dash].join("/"), tag)),
+			_ => None
+		}
+	}
+	else {
+		None
+	}
+}
+
+// The notes written for a release on the forge
+#[derive(Debug, Clone)]
+pub struct ReleaseNote {
+	pub tag: String,
+	pub body: String,
+	pub url: Option<String>
+}
+
+#[derive(Deserialize)]
+struct GhReleaseNote {
+	tag_name: String,
+	body: Option<String>,
+	html_url: Option<String>
+}
+
+#[derive(Deserialize)]
+struct GlReleaseNote {
+	tag_name: String,
+	description: Option<String>
+}
+
+// Newest first, only GitHub and GitLab have them
+pub fn fetch_release_notes(url: &Url, config: &Config) -> Result<Vec<ReleaseNote>, MetadataError> {
+	if is_github(url) {
+		let (author, repo) = owner_and_repo(url).ok_or(MetadataError::UnknownProject)?;
+		let token = config.github_token();
+		let notes: Vec<GhReleaseNote> = get_json_with(&format!("https://api.github.com/repos/{}/{}/releases?per_page=30", author, repo), token.as_deref())?;
+		Ok(notes.into_iter().map(|note| ReleaseNote {tag: note.tag_name, body: note.body.unwrap_or_default(), url: note.html_url}).collect())
+	}
+	else if GitLab.handles(url) {
+		let api_url = gitlab_api_url(url)?;
+		let web_url = api_url.replacen("/api/v4/projects/", "/", 1).replace("%2F", "/");
+		let notes: Vec<GlReleaseNote> = get_json(&format!("{}/releases", api_url))?;
+		Ok(notes.into_iter().map(|note| {
+			let url = Some(format!("{}/-/releases/{}", web_url, note.tag_name));
+			ReleaseNote {tag: note.tag_name, body: note.description.unwrap_or_default(), url}
+		}).collect())
+	}
+	else {
+		Err(MetadataError::UnknownProject)
+	}
+}
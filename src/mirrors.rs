@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use reqwest::Url;
+
+use crate::worker::Job;
+
+// Hosts known to serve the same files over HTTPS, no need to ask them
+const HTTPS_HOSTS: &[&str] = &[
+	"github.com", "gitlab.com", "bitbucket.org",
+	"gitlab.gnome.org", "download.gnome.org",
+	"gitlab.freedesktop.org", "xorg.freedesktop.org", "www.x.org",
+	"downloads.sourceforge.net",
+	"ftp.gnu.org", "ftpmirror.gnu.org",
+	"cdn.kernel.org", "www.kernel.org",
+	"download.kde.org",
+	"files.pythonhosted.org", "pypi.org",
+	"crates.io", "static.crates.io", "registry.npmjs.org"
+];
+// Archives of these are whatever the branch has right now
+const BRANCHES: &[&str] = &["master", "main", "develop", "devel", "trunk", "HEAD"];
+const HTTPS_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn segments(url: &Url) -> Vec<&str> {
+	url.path_segments().map_or(Vec::new(), |segments| segments.filter(|segment| !segment.is_empty()).collect())
+}
+
+// sourceforge.net/projects/foo/files/1.0/foo-1.0.tar.gz/download and the
+// mirrors (netcologne.dl.sourceforge.net/project/...) are all
+// downloads.sourceforge.net/project/foo/1.0/foo-1.0.tar.gz
+fn sourceforge(host: &str, segments: &[&str]) -> Option<String> {
+	let segments = match segments.split_last() {
+		Some((&"download", rest)) => rest,
+		_ => segments
+	};
+	let (project, path) = match (host, segments) {
+		("sourceforge.net", ["projects", project, "files", path @ ..]) | ("www.sourceforge.net", ["projects", project, "files", path @ ..]) => (project, path),
+		("prdownloads.sourceforge.net", [project, path @ ..]) => (project, path),
+		(_, ["project", project, path @ ..]) if host.ends_with(".sourceforge.net") => (project, path),
+		_ => return None
+	};
+	if path.is_empty() {
+		return None;
+	}
+	Some(format!("https://downloads.sourceforge.net/project/{}/{}", project, path.join("/")))
+}
+
+// codeload.github.com/foo/bar/tar.gz/refs/tags/v1.0 and
+// github.com/foo/bar/archive/refs/tags/v1.0.tar.gz are both
+// github.com/foo/bar/archive/v1.0.tar.gz, which is what Solus uses
+fn github(host: &str, segments: &[&str]) -> Option<String> {
+	match (host, segments) {
+		("codeload.github.com", [owner, repo, format, reference @ ..]) if !reference.is_empty() => {
+			let ext = match *format {
+				"tar.gz" | "legacy.tar.gz" => "tar.gz",
+				"zip" | "legacy.zip" => "zip",
+				_ => return None
+			};
+			let reference = reference.join("/");
+			Some(format!("https://github.com/{}/{}/archive/{}.{}", owner, repo, reference.trim_start_matches("refs/tags/"), ext))
+		}
+		("github.com", [owner, repo, "archive", "refs", "tags", file @ ..]) if !file.is_empty() => {
+			Some(format!("https://github.com/{}/{}/archive/{}", owner, repo, file.join("/")))
+		}
+		_ => None
+	}
+}
+
+// Mirrors and the old FTP have GNOME's sources under .../gnome/sources/, the
+// main site has them under /sources/
+fn gnome(host: &str, segments: &[&str]) -> Option<String> {
+	let is_gnome_host = host == "download.gnome.org" || host == "ftp.gnome.org" || host == "download-fallback.gnome.org";
+	let start = if is_gnome_host && segments.first() == Some(&"sources") {
+		1
+	}
+	else {
+		segments.windows(2).position(|pair| pair[0].eq_ignore_ascii_case("gnome") && pair[1] == "sources")? + 2
+	};
+	if start >= segments.len() || (host == "download.gnome.org" && start == 1) {
+		return None;
+	}
+	Some(format!("https://download.gnome.org/sources/{}", segments[start..].join("/")))
+}
+
+// The preferred form of url, without asking anyone
+pub fn rewrite(url: &Url) -> Url {
+	let host = url.host_str().unwrap_or("").to_lowercase();
+	let segments = segments(url);
+	let rewritten = sourceforge(&host, &segments)
+		.or_else(|| github(&host, &segments))
+		.or_else(|| gnome(&host, &segments))
+		.and_then(|rewritten| Url::parse(&rewritten).ok());
+
+	let mut url = rewritten.unwrap_or_else(|| url.clone());
+	if url.scheme() == "http" && url.host_str().map_or(false, |host| HTTPS_HOSTS.contains(&host)) {
+		let _ = url.set_scheme("https");
+	}
+	url
+}
+
+// Whether the server gives the same file over HTTPS
+fn https_works(url: &Url) -> Option<Url> {
+	let mut https = url.clone();
+	https.set_scheme("https").ok()?;
+	let client = reqwest::blocking::Client::builder().timeout(HTTPS_TIMEOUT).build().ok()?;
+	client.head(https.clone()).send().ok()?.error_for_status().ok()?;
+	Some(https)
+}
+
+// The form that goes into package.yml: the main site instead of a mirror and
+// HTTPS whenever the server has it
+pub fn canonical(url: &Url, job: &Job) -> Url {
+	let url = rewrite(url);
+	if url.scheme() != "http" {
+		return url;
+	}
+	job.step("Checking whether the source is on HTTPS");
+	https_works(&url).unwrap_or(url)
+}
+
+fn archive_reference<'a>(file: &'a str) -> &'a str {
+	[".tar.gz", ".tar.bz2", ".tar.xz", ".tgz", ".zip", ".tar"].iter()
+		.find_map(|ext| file.strip_suffix(ext))
+		.unwrap_or(file)
+}
+
+// Archives made on the fly by the forge: the checksum can change when they
+// change how they're made, and if it's a branch, the contents too
+pub fn unstable(url_str: &str) -> Option<String> {
+	let url = Url::parse(url_str.trim()).ok()?;
+	let host = url.host_str()?.to_lowercase();
+	let segments = segments(&url);
+
+	let (forge, reference) = match (host.as_str(), segments.as_slice()) {
+		("github.com", [_, _, "archive", reference @ ..]) => ("GitHub", reference.join("/")),
+		("codeload.github.com", [_, _, _, reference @ ..]) => ("GitHub", reference.join("/")),
+		("bitbucket.org", [_, _, "get", reference @ ..]) => ("Bitbucket", reference.join("/")),
+		_ => {
+			// GitLab (and gitlab.gnome.org...), cgit and Gitea
+			let pos = segments.iter().position(|segment| *segment == "archive" || *segment == "snapshot")?;
+			let is_gitlab = pos > 0 && segments[pos - 1] == "-";
+			if !is_gitlab && segments[pos] == "archive" && !host.contains("gitea") && !host.contains("codeberg") {
+				return None;
+			}
+			("The forge", segments[pos + 1..].join("/"))
+		}
+	};
+	if reference.is_empty() {
+		return None;
+	}
+
+	let reference = archive_reference(&reference);
+	let branch = reference.starts_with("refs/heads/") || BRANCHES.iter().any(|branch| reference == *branch || reference.ends_with(&format!("-{}", branch)));
+	Some(if branch {
+		format!("{} makes this archive from a branch, what's in it changes with every commit, point to a tag or a release", forge)
+	}
+	else {
+		format!("{} makes this archive on the fly, its checksum can change, a release asset is safer if there's one", forge)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rewritten(url: &str) -> String {
+		rewrite(&Url::parse(url).unwrap()).to_string()
+	}
+
+	#[test]
+	fn sourceforge_mirrors() {
+		let canonical = "https://downloads.sourceforge.net/project/foo/1.0/foo-1.0.tar.gz";
+		assert_eq!(rewritten("https://sourceforge.net/projects/foo/files/1.0/foo-1.0.tar.gz/download"), canonical);
+		assert_eq!(rewritten("http://netcologne.dl.sourceforge.net/project/foo/1.0/foo-1.0.tar.gz"), canonical);
+		assert_eq!(rewritten("https://prdownloads.sourceforge.net/foo/1.0/foo-1.0.tar.gz"), canonical);
+		assert_eq!(rewritten(canonical), canonical);
+	}
+
+	#[test]
+	fn github_archives() {
+		let canonical = "https://github.com/foo/bar/archive/v1.0.tar.gz";
+		assert_eq!(rewritten("https://codeload.github.com/foo/bar/tar.gz/refs/tags/v1.0"), canonical);
+		assert_eq!(rewritten("https://github.com/foo/bar/archive/refs/tags/v1.0.tar.gz"), canonical);
+		assert_eq!(rewritten("https://codeload.github.com/foo/bar/legacy.zip/v1.0"), "https://github.com/foo/bar/archive/v1.0.zip");
+		// Release assets are left alone
+		let asset = "https://github.com/foo/bar/releases/download/v1.0/bar-1.0.tar.xz";
+		assert_eq!(rewritten(asset), asset);
+	}
+
+	#[test]
+	fn gnome_and_https() {
+		let canonical = "https://download.gnome.org/sources/gedit/3.36/gedit-3.36.2.tar.xz";
+		assert_eq!(rewritten("http://ftp.gnome.org/pub/GNOME/sources/gedit/3.36/gedit-3.36.2.tar.xz"), canonical);
+		assert_eq!(rewritten("https://mirror.example.org/gnome/sources/gedit/3.36/gedit-3.36.2.tar.xz"), canonical);
+		assert_eq!(rewritten("http://download.gnome.org/sources/gedit/3.36/gedit-3.36.2.tar.xz"), canonical);
+		assert_eq!(rewritten("http://ftp.gnu.org/gnu/hello/hello-2.10.tar.gz"), "https://ftp.gnu.org/gnu/hello/hello-2.10.tar.gz");
+		// Unknown hosts may not have HTTPS
+		assert_eq!(rewritten("http://example.org/foo-1.0.tar.gz"), "http://example.org/foo-1.0.tar.gz");
+	}
+
+	#[test]
+	fn generated_archives_are_unstable() {
+		for url in ["https://github.com/foo/bar/archive/v1.0.tar.gz", "https://github.com/foo/bar/archive/master.tar.gz",
+			"https://codeload.github.com/foo/bar/tar.gz/refs/tags/v1.0", "https://gitlab.com/foo/bar/-/archive/v1.0/bar-v1.0.tar.gz",
+			"https://bitbucket.org/foo/bar/get/develop.zip", "https://git.example.org/bar.git/snapshot/bar-1.0.tar.gz"].iter() {
+			assert!(unstable(url).is_some(), "{}", url);
+		}
+		for url in ["https://github.com/foo/bar/releases/download/v1.0/bar-1.0.tar.xz", "https://example.org/archive/master.tar.gz", "not a url"].iter() {
+			assert!(unstable(url).is_none(), "{}", url);
+		}
+		// Worse if it's a branch
+		assert!(unstable("https://gitlab.com/foo/bar/-/archive/master/bar-master.tar.gz").unwrap().contains("branch"));
+		assert!(unstable("https://github.com/foo/bar/archive/refs/heads/dev-1.tar.gz").unwrap().contains("branch"));
+		assert!(!unstable("https://github.com/foo/bar/archive/v1.0.tar.gz").unwrap().contains("branch"));
+	}
+}
@@ -0,0 +1,430 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::DialogFlags;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use relm::Channel;
+
+use crate::error::{Error, Result};
+use crate::worker;
+
+// Patches live in files/ next to package.yml, ypkg calls it $pkgfiles
+const FILES_DIR: &str = "files";
+const PATCH_ENDINGS: &[&str] = &[".patch", ".diff"];
+
+// What the buttons under the list answer to the dialog with
+const ADD_FILE: u16 = 0;
+const ADD_URL: u16 = 1;
+const UP: u16 = 2;
+const DOWN: u16 = 3;
+const REMOVE: u16 = 4;
+
+static REG_PATCH: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*%patch\s+(?:-\S+\s+)*<\s*\$pkgfiles/(\S+)\s*$").unwrap());
+
+// The patches setup applies, in order
+pub fn applied_patches(setup: &str) -> Vec<String> {
+	setup.lines()
+		.filter_map(|line| REG_PATCH.captures(line).map(|caps| caps[1].to_string()))
+		.collect()
+}
+
+// setup with its %patch lines replaced by patches, where the first one was or
+// at the start if there was none. Everything else stays as it was.
+pub fn set_patches(setup: &str, patches: &[String]) -> String {
+	let lines = setup.lines().collect::<Vec<_>>();
+	let first = lines.iter().position(|line| REG_PATCH.is_match(line)).unwrap_or(0);
+	let patch_lines = patches.iter().map(|patch| format!("%patch -p1 < $pkgfiles/{}", patch)).collect::<Vec<_>>();
+
+	let mut out = lines[..first].iter().map(|line| line.to_string()).collect::<Vec<_>>();
+	out.extend(patch_lines);
+	out.extend(lines[first..].iter().filter(|line| !REG_PATCH.is_match(line)).map(|line| line.to_string()));
+
+	let mut text = out.join("\n");
+	if setup.ends_with('\n') || (setup.is_empty() && !text.is_empty()) {
+		text.push('\n');
+	}
+	text
+}
+
+fn is_patch(path: &Path) -> bool {
+	let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("").to_lowercase();
+	PATCH_ENDINGS.iter().any(|ending| name.ends_with(ending))
+}
+
+fn collect_patches(dir: &Path, files_dir: &Path, found: &mut Vec<String>) {
+	let entries = match std::fs::read_dir(dir) {
+		Ok(entries) => entries,
+		Err(_) => return
+	};
+
+	for entry in entries.filter_map(|entry| entry.ok()) {
+		let path = entry.path();
+		// Without following links, a link to a parent folder would never end
+		if entry.file_type().map_or(false, |file_type| file_type.is_dir()) {
+			collect_patches(&path, files_dir, found);
+		}
+		else if is_patch(&path) {
+			if let Ok(relative) = path.strip_prefix(files_dir) {
+				found.push(relative.to_string_lossy().to_string());
+			}
+		}
+	}
+}
+
+// Every patch in files/, relative to it
+pub fn patch_files(pkg_dir: &Path) -> Vec<String> {
+	let files_dir = pkg_dir.join(FILES_DIR);
+	let mut found = Vec::new();
+	collect_patches(&files_dir, &files_dir, &mut found);
+	found.sort();
+	found
+}
+
+// Patches added and removed in the dialog, files/ only changes when the
+// package is saved
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PendingFiles {
+	// Name in files/ and contents
+	added: Vec<(String, Vec<u8>)>,
+	removed: Vec<String>
+}
+
+impl PendingFiles {
+	fn is_added(&self, name: &str) -> bool {
+		self.added.iter().any(|(added, _)| added == name)
+	}
+
+	fn add(&mut self, name: String, contents: Vec<u8>) {
+		self.removed.retain(|removed| *removed != name);
+		self.added.push((name, contents));
+	}
+
+	fn remove(&mut self, name: &str) {
+		if self.is_added(name) {
+			self.added.retain(|(added, _)| added != name);
+		}
+		else {
+			self.removed.push(name.to_string());
+		}
+	}
+
+	// Writes the changes into pkg_dir/files
+	pub fn apply(&self, pkg_dir: &Path) -> Result<()> {
+		let files_dir = pkg_dir.join(FILES_DIR);
+		for name in self.removed.iter() {
+			match std::fs::remove_file(files_dir.join(name)) {
+				Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+				_ => {}
+			}
+		}
+		for (name, contents) in self.added.iter() {
+			let dest = files_dir.join(name);
+			if let Some(dir) = dest.parent() {
+				std::fs::create_dir_all(dir)?;
+			}
+			std::fs::write(&dest, contents)?;
+		}
+		Ok(())
+	}
+}
+
+// The patches in files/ once pending is applied
+fn current_files(pkg_dir: &Path, pending: &PendingFiles) -> Vec<String> {
+	let mut files = patch_files(pkg_dir).into_iter().filter(|name| !pending.removed.contains(name)).collect::<Vec<_>>();
+	files.extend(pending.added.iter().map(|(name, _)| name.clone()));
+	files
+}
+
+fn check_new_name(pkg_dir: &Path, pending: &PendingFiles, name: &str) -> Result<()> {
+	if current_files(pkg_dir, pending).iter().any(|file| file == name) {
+		Err(Error::parse(format!("There's already a patch called {} in files/", name)))
+	}
+	else {
+		Ok(())
+	}
+}
+
+fn add_from_file(src: &Path) -> Result<(String, Vec<u8>)> {
+	let name = src.file_name().and_then(|name| name.to_str()).ok_or_else(|| Error::parse(format!("{} is not a file", src.display())))?;
+	Ok((name.to_string(), std::fs::read(src)?))
+}
+
+enum DownloadMsg {
+	Progress(String, Option<f64>),
+	Done(Result<Vec<u8>>)
+}
+
+// Downloads in the background with a progress dialog that can cancel it,
+// GTK keeps running meanwhile
+fn download(parent: &gtk::Dialog, url: &str) -> Result<Vec<u8>> {
+	let dialog = gtk::Dialog::new_with_buttons(Some("Downloading the patch"), Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, &[("Cancel", gtk::ResponseType::Cancel)]);
+	let progress = gtk::ProgressBar::new();
+	progress.set_show_text(true);
+	dialog.get_content_area().pack_start(&progress, false, false, 12);
+	dialog.set_default_size(400, -1);
+
+	let result = Rc::new(RefCell::new(None));
+	let (_channel, sender) = {
+		let result = result.clone();
+		let progress = progress.clone();
+		Channel::new(move |msg| match msg {
+			DownloadMsg::Progress(text, fraction) => {
+				progress.set_text(Some(&text));
+				match fraction {
+					Some(fraction) => progress.set_fraction(fraction),
+					None => progress.pulse()
+				}
+			}
+			DownloadMsg::Done(res) => *result.borrow_mut() = Some(res)
+		})
+	};
+
+	let url = url.to_string();
+	let job = worker::spawn(sender, DownloadMsg::Progress, move |job| job.download(&url), DownloadMsg::Done);
+	dialog.connect_response(move |_, _| job.cancel());
+	dialog.show_all();
+
+	// Waits for events, the channel wakes it up when the download is done
+	while result.borrow().is_none() {
+		gtk::main_iteration();
+	}
+	dialog.destroy();
+	let res = result.borrow_mut().take();
+	res.unwrap()
+}
+
+fn add_from_url(parent: &gtk::Dialog, url: &str) -> Result<(String, Vec<u8>)> {
+	let url = crate::parse_url(url)?;
+	let name = url.path_segments().and_then(|segments| segments.last()).filter(|name| !name.is_empty())
+		.map(|name| if is_patch(Path::new(name)) {name.to_string()} else {format!("{}.patch", name)})
+		.ok_or_else(|| Error::parse(format!("{} doesn't point to a file", url)))?;
+
+	Ok((name, download(parent, url.as_str())?))
+}
+
+fn ask_text(parent: &gtk::Dialog, question: &str) -> Option<String> {
+	let dialog = gtk::MessageDialog::new(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::OkCancel, question);
+	let entry = gtk::Entry::new();
+	entry.set_activates_default(true);
+	dialog.set_default_response(gtk::ResponseType::Ok);
+	dialog.get_content_area().pack_end(&entry, false, false, 0);
+	dialog.show_all();
+	let response = dialog.run();
+	let text = entry.get_text().map(|text| text.to_string()).unwrap_or_default();
+	dialog.destroy();
+
+	if response == gtk::ResponseType::Ok && !text.trim().is_empty() {
+		Some(text.trim().to_string())
+	}
+	else {
+		None
+	}
+}
+
+fn ask_patch_file(parent: &gtk::Dialog) -> Option<PathBuf> {
+	let chooser = gtk::FileChooserDialog::with_buttons(Some("Select a patch"), Some(parent), gtk::FileChooserAction::Open, &[("Add", gtk::ResponseType::Ok)]);
+	chooser.show_all();
+	let response = chooser.run();
+	let file = chooser.get_filename();
+	chooser.destroy();
+
+	if response == gtk::ResponseType::Ok {file} else {None}
+}
+
+fn show_error(parent: &gtk::Dialog, title: &str, err: &Error) {
+	let dialog = gtk::MessageDialog::new(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Error, gtk::ButtonsType::Ok, &format!("{}: {}", title, err));
+	dialog.run();
+	dialog.destroy();
+}
+
+fn confirm(parent: &gtk::Dialog, question: &str) -> bool {
+	let dialog = gtk::MessageDialog::new(Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, gtk::MessageType::Question, gtk::ButtonsType::OkCancel, question);
+	let response = dialog.run();
+	dialog.destroy();
+	response == gtk::ResponseType::Ok
+}
+
+// Rows of the list, applied patches first and in order
+struct PatchRow {
+	name: String,
+	applied: bool
+}
+
+fn fill_list(list: &gtk::ListBox, rows: &[PatchRow], checks: &mut Vec<gtk::CheckButton>) {
+	for child in list.get_children() {
+		list.remove(&child);
+	}
+	checks.clear();
+
+	for row in rows.iter() {
+		let check = gtk::CheckButton::new_with_label(&row.name);
+		check.set_active(row.applied);
+		check.set_tooltip_text(Some("Apply it in setup"));
+		list.add(&check);
+		checks.push(check);
+	}
+	list.show_all();
+}
+
+// Lets the user choose which patches of pkg_dir/files are applied and in
+// which order, patches is changed to it. Patches added or removed go to
+// pending, to be applied when saving. Returns whether anything changed.
+pub fn show_patches(parent: &gtk::Window, pkg_dir: &Path, patches: &mut Vec<String>, pending: &mut PendingFiles) -> bool {
+	let dialog = gtk::Dialog::new_with_buttons(Some("Patches"), Some(parent), DialogFlags::MODAL | DialogFlags::USE_HEADER_BAR, &[("Cancel", gtk::ResponseType::Cancel), ("Ok", gtk::ResponseType::Ok)]);
+	dialog.set_default_size(500, 400);
+
+	let mut new_pending = pending.clone();
+	let mut rows = patches.iter().map(|name| PatchRow {name: name.clone(), applied: true}).collect::<Vec<_>>();
+	for name in current_files(pkg_dir, pending) {
+		if !patches.contains(&name) {
+			rows.push(PatchRow {name, applied: false});
+		}
+	}
+
+	let list = gtk::ListBox::new();
+	let scroll = gtk::ScrolledWindow::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+	scroll.set_vexpand(true);
+	scroll.add(&list);
+
+	let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+	for (action, label) in [(ADD_FILE, "Add file"), (ADD_URL, "Add URL"), (UP, "Up"), (DOWN, "Down"), (REMOVE, "Remove")].iter() {
+		let button = gtk::Button::new_with_label(label);
+		let dialog = dialog.clone();
+		let action = *action;
+		button.connect_clicked(move |_| dialog.response(gtk::ResponseType::Other(action)));
+		buttons.pack_start(&button, false, false, 0);
+	}
+
+	dialog.get_content_area().pack_start(&scroll, true, true, 0);
+	dialog.get_content_area().pack_start(&buttons, false, false, 6);
+	dialog.show_all();
+
+	let mut checks = Vec::new();
+	fill_list(&list, &rows, &mut checks);
+
+	// The action buttons answer with Other(index) and the dialog runs again
+	let response = loop {
+		let response = dialog.run();
+		for (row, check) in rows.iter_mut().zip(checks.iter()) {
+			row.applied = check.get_active();
+		}
+		let selected = list.get_selected_row().map(|row| row.get_index() as usize);
+
+		let action = match response {
+			gtk::ResponseType::Other(action) => action,
+			other => break other
+		};
+
+		let mut select = selected;
+		match (action, selected) {
+			(ADD_FILE, _) | (ADD_URL, _) => {
+				let added = if action == ADD_FILE {
+					ask_patch_file(&dialog).map(|file| add_from_file(&file))
+				}
+				else {
+					ask_text(&dialog, "URL of the patch").map(|url| add_from_url(&dialog, &url))
+				};
+				let added = added.map(|added| added.and_then(|(name, contents)| {
+					check_new_name(pkg_dir, &new_pending, &name)?;
+					Ok((name, contents))
+				}));
+				match added {
+					Some(Ok((name, contents))) => {
+						new_pending.add(name.clone(), contents);
+						// New patches go after the ones already applied
+						let pos = rows.iter().position(|row| !row.applied).unwrap_or(rows.len());
+						rows.insert(pos, PatchRow {name, applied: true});
+						select = Some(pos);
+					}
+					Some(Err(Error::Cancelled)) | None => {}
+					Some(Err(err)) => show_error(&dialog, "Couldn't add the patch", &err)
+				}
+			}
+			(UP, Some(i)) if i > 0 => {
+				rows.swap(i, i - 1);
+				select = Some(i - 1);
+			}
+			(DOWN, Some(i)) if i + 1 < rows.len() => {
+				rows.swap(i, i + 1);
+				select = Some(i + 1);
+			}
+			(REMOVE, Some(i)) => {
+				if confirm(&dialog, &format!("Delete {} from files/ when saving?", rows[i].name)) {
+					new_pending.remove(&rows[i].name);
+					rows.remove(i);
+				}
+				select = None;
+			}
+			_ => {}
+		}
+
+		fill_list(&list, &rows, &mut checks);
+		if let Some(row) = select.and_then(|i| list.get_row_at_index(i as i32)) {
+			list.select_row(Some(&row));
+		}
+	};
+	dialog.destroy();
+
+	let new_patches = rows.into_iter().filter(|row| row.applied).map(|row| row.name).collect::<Vec<_>>();
+	if response == gtk::ResponseType::Ok && (new_patches != *patches || new_pending != *pending) {
+		*patches = new_patches;
+		*pending = new_pending;
+		true
+	}
+	else {
+		false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SETUP: &str = "%patch -p1 < $pkgfiles/fix-build.patch\n  %patch -p1 -N < $pkgfiles/0001-foo.patch\n%configure --disable-static\n";
+
+	fn names(patches: &[&str]) -> Vec<String> {
+		patches.iter().map(|patch| patch.to_string()).collect()
+	}
+
+	#[test]
+	fn finds_applied_patches() {
+		assert_eq!(applied_patches(SETUP), names(&["fix-build.patch", "0001-foo.patch"]));
+		assert_eq!(applied_patches("%patch -p1 < $pkgfiles/security/CVE-2020-1234.patch"), names(&["security/CVE-2020-1234.patch"]));
+		// Not from files/, packmak leaves those alone
+		assert!(applied_patches("%patch -p1 < ../other.patch\n%apply_patches\n").is_empty());
+	}
+
+	#[test]
+	fn set_patches_round_trips() {
+		let patches = names(&["0001-foo.patch", "new.patch"]);
+		let setup = set_patches(SETUP, &patches);
+		assert_eq!(applied_patches(&setup), patches);
+		assert_eq!(setup, "%patch -p1 < $pkgfiles/0001-foo.patch\n%patch -p1 < $pkgfiles/new.patch\n%configure --disable-static\n");
+		assert_eq!(set_patches(&setup, &names(&["fix-build.patch", "0001-foo.patch"])), set_patches(SETUP, &applied_patches(SETUP)));
+	}
+
+	#[test]
+	fn set_patches_keeps_the_rest() {
+		// Where the first patch was, or at the start
+		assert_eq!(set_patches("cd src\n%patch -p1 < $pkgfiles/a.patch\n%configure", &names(&["b.patch"])), "cd src\n%patch -p1 < $pkgfiles/b.patch\n%configure");
+		assert_eq!(set_patches("%configure\n", &names(&["a.patch"])), "%patch -p1 < $pkgfiles/a.patch\n%configure\n");
+		assert_eq!(set_patches("", &names(&["a.patch"])), "%patch -p1 < $pkgfiles/a.patch\n");
+		assert_eq!(set_patches(SETUP, &[]), "%configure --disable-static\n");
+	}
+
+	#[test]
+	fn pending_files_cancel_out() {
+		let mut pending = PendingFiles::default();
+		pending.add("a.patch".to_string(), b"a".to_vec());
+		pending.remove("a.patch");
+		assert_eq!(pending, PendingFiles::default());
+
+		pending.remove("old.patch");
+		pending.add("old.patch".to_string(), b"new".to_vec());
+		assert!(pending.removed.is_empty());
+		assert!(pending.is_added("old.patch"));
+	}
+}
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use roxmltree::Document;
+
+use crate::git;
+
+// The releases a package.yml is known to have had, any change has to go
+// one above all of them
+#[derive(Debug, Clone, Copy)]
+pub struct ReleaseInfo {
+	// What package.yml says right now
+	pub file: u16,
+	// What was last committed to git, if it's in a repo
+	pub committed: Option<u16>,
+	// The last one that was built, from pspec_x86_64.xml
+	pub pspec: Option<u16>
+}
+
+impl ReleaseInfo {
+	pub fn read(pkg_path: &Path, file: u16) -> Self {
+		let dir = pkg_path.parent().unwrap_or_else(|| Path::new("."));
+		let pspec = std::fs::read_to_string(dir.join("pspec_x86_64.xml")).ok().and_then(|xml| pspec_release(&xml));
+		ReleaseInfo {file, committed: committed_release(pkg_path), pspec}
+	}
+
+	// The release changes are counted from. The committed one is preferred so
+	// that saving several times doesn't keep going up.
+	pub fn base(&self) -> u16 {
+		let base = self.committed.unwrap_or(self.file);
+		self.pspec.map_or(base, |pspec| base.max(pspec))
+	}
+
+	// pspec_x86_64.xml is ahead of package.yml, someone forgot to update it
+	pub fn behind_pspec(&self) -> Option<u16> {
+		self.pspec.filter(|pspec| *pspec > self.file)
+	}
+}
+
+fn committed_release(pkg_path: &Path) -> Option<u16> {
+	let yaml: serde_yaml::Value = serde_yaml::from_str(&git::committed_file(pkg_path)?).ok()?;
+	yaml.get("release")?.as_u64().map(|release| release as u16)
+}
+
+// Every build adds an <Update release="N"> to the History
+fn pspec_release(xml: &str) -> Option<u16> {
+	let doc = Document::parse(xml).ok()?;
+	doc.descendants()
+		.filter(|node| node.has_tag_name("Update"))
+		.filter_map(|node| node.attribute("release")?.parse::<u16>().ok())
+		.max()
+}
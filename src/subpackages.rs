@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+// A value in rundeps, replaces or patterns can be a single string or a list
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum OneOrMany {
+	One(String),
+	Many(Vec<String>)
+}
+
+impl OneOrMany {
+	fn into_vec(self) -> Vec<String> {
+		match self {
+			OneOrMany::One(value) => vec![value],
+			OneOrMany::Many(values) => values
+		}
+	}
+
+	fn from_vec(mut values: Vec<String>) -> Self {
+		if values.len() == 1 {
+			OneOrMany::One(values.remove(0))
+		}
+		else {
+			OneOrMany::Many(values)
+		}
+	}
+}
+
+// Entries of rundeps, replaces and patterns: a plain string is for the main
+// package, a map is keyed by subpackage (devel, docs...) or by a whole package
+// name with a caret (^foo)
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum SubpkgEntry {
+	Main(String),
+	Sub(BTreeMap<String, OneOrMany>)
+}
+
+pub type SubpkgList = Vec<SubpkgEntry>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subpackage {
+	pub patterns: Vec<String>,
+	pub rundeps: Vec<String>,
+	pub replaces: Vec<String>
+}
+
+impl Subpackage {
+	pub fn is_empty(&self) -> bool {
+		self.patterns.is_empty() && self.rundeps.is_empty() && self.replaces.is_empty()
+	}
+}
+
+// Every subpackage, the main package is the one with an empty name
+pub type Subpackages = BTreeMap<String, Subpackage>;
+
+fn spread<F>(list: Option<SubpkgList>, subpkgs: &mut Subpackages, field: F)
+	where F: Fn(&mut Subpackage) -> &mut Vec<String>
+{
+	for entry in list.unwrap_or_default() {
+		match entry {
+			SubpkgEntry::Main(value) => field(subpkgs.entry(String::new()).or_default()).push(value),
+			SubpkgEntry::Sub(map) => {
+				for (name, values) in map {
+					field(subpkgs.entry(name).or_default()).extend(values.into_vec());
+				}
+			}
+		}
+	}
+}
+
+pub fn from_lists(patterns: Option<SubpkgList>, rundeps: Option<SubpkgList>, replaces: Option<SubpkgList>) -> Subpackages {
+	let mut subpkgs = Subpackages::new();
+	spread(patterns, &mut subpkgs, |subpkg| &mut subpkg.patterns);
+	spread(rundeps, &mut subpkgs, |subpkg| &mut subpkg.rundeps);
+	spread(replaces, &mut subpkgs, |subpkg| &mut subpkg.replaces);
+	subpkgs
+}
+
+// Back to the package.yml format, None when there's nothing so that the key
+// isn't written
+fn gather<F>(subpkgs: &Subpackages, field: F) -> Option<SubpkgList>
+	where F: Fn(&Subpackage) -> &Vec<String>
+{
+	let mut list = SubpkgList::new();
+	for (name, subpkg) in subpkgs.iter().filter(|(_, subpkg)| !field(subpkg).is_empty()) {
+		let values = field(subpkg).clone();
+		if name.is_empty() {
+			list.extend(values.into_iter().map(SubpkgEntry::Main));
+		}
+		else {
+			let mut map = BTreeMap::new();
+			map.insert(name.clone(), OneOrMany::from_vec(values));
+			list.push(SubpkgEntry::Sub(map));
+		}
+	}
+
+	if list.is_empty() {None} else {Some(list)}
+}
+
+// (patterns, rundeps, replaces)
+pub fn to_lists(subpkgs: &Subpackages) -> (Option<SubpkgList>, Option<SubpkgList>, Option<SubpkgList>) {
+	(
+		gather(subpkgs, |subpkg| &subpkg.patterns),
+		gather(subpkgs, |subpkg| &subpkg.rundeps),
+		gather(subpkgs, |subpkg| &subpkg.replaces)
+	)
+}
+
+// Whether both have the same contents, subpackages with nothing in them don't count
+pub fn same(a: &Subpackages, b: &Subpackages) -> bool {
+	let non_empty = |subpkgs: &Subpackages| subpkgs.iter().filter(|(_, subpkg)| !subpkg.is_empty()).map(|(name, subpkg)| (name.clone(), subpkg.clone())).collect::<Vec<_>>();
+	non_empty(a) == non_empty(b)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Serialize, Deserialize)]
+	struct Lists {
+		#[serde(skip_serializing_if = "Option::is_none")]
+		patterns: Option<SubpkgList>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		rundeps: Option<SubpkgList>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		replaces: Option<SubpkgList>
+	}
+
+	const YAML: &str = "
+patterns:
+  - /usr/share/foo
+  - devel:
+      - /usr/lib64/lib*.so
+      - /usr/include
+  - docs: /usr/share/doc
+rundeps:
+  - python3
+  - ^libfoo: foo
+  - devel: libbar-devel
+replaces:
+  - devel: foo-headers
+";
+
+	fn subpackages(yaml: &str) -> Subpackages {
+		let lists: Lists = serde_yaml::from_str(yaml).unwrap();
+		from_lists(lists.patterns, lists.rundeps, lists.replaces)
+	}
+
+	#[test]
+	fn spreads_by_subpackage() {
+		let subpkgs = subpackages(YAML);
+		assert_eq!(subpkgs.keys().collect::<Vec<_>>(), ["", "^libfoo", "devel", "docs"]);
+		assert_eq!(subpkgs[""].patterns, ["/usr/share/foo"]);
+		assert_eq!(subpkgs[""].rundeps, ["python3"]);
+		assert_eq!(subpkgs["devel"], Subpackage {
+			patterns: vec!["/usr/lib64/lib*.so".to_string(), "/usr/include".to_string()],
+			rundeps: vec!["libbar-devel".to_string()],
+			replaces: vec!["foo-headers".to_string()]
+		});
+		assert_eq!(subpkgs["^libfoo"].rundeps, ["foo"]);
+	}
+
+	#[test]
+	fn round_trips() {
+		let (patterns, rundeps, replaces) = to_lists(&subpackages(YAML));
+		let written = serde_yaml::to_string(&Lists {patterns, rundeps, replaces}).unwrap();
+		assert_eq!(subpackages(&written), subpackages(YAML));
+	}
+
+	#[test]
+	fn nothing_isnt_written() {
+		let mut subpkgs = subpackages("patterns:\n  - docs: /usr/share/doc\n");
+		subpkgs.get_mut("docs").unwrap().patterns.clear();
+		assert_eq!(to_lists(&subpkgs), (None, None, None));
+		assert!(same(&subpkgs, &Subpackages::new()));
+		assert!(!same(&subpackages(YAML), &Subpackages::new()));
+	}
+}
@@ -84,3 +84,30 @@ pub const COMPONENTS: &[&str] = &[
 ];
 
 
+// SPDX identifiers accepted by the license lint, the ones above plus other
+// common ones that aren't offered in the combo
+pub const SPDX_LICENSES: &[&str] = &[
+	"0BSD", "AFL-2.1", "AFL-3.0", "AGPL-3.0-only", "AGPL-3.0-or-later", "Apache-1.1", "Apache-2.0",
+	"Artistic-1.0", "Artistic-1.0-Perl", "Artistic-2.0", "BSD-1-Clause", "BSD-2-Clause", "BSD-3-Clause",
+	"BSD-4-Clause", "BSL-1.0", "CC-BY-3.0", "CC-BY-4.0", "CC-BY-SA-3.0", "CC-BY-SA-4.0", "CC0-1.0",
+	"CDDL-1.0", "CDDL-1.1", "CECILL-2.1", "EPL-1.0", "EPL-2.0", "EUPL-1.2", "FTL", "GFDL-1.3-only",
+	"GFDL-1.3-or-later", "GPL-1.0-or-later", "GPL-2.0-only", "GPL-2.0-or-later", "GPL-3.0-only",
+	"GPL-3.0-or-later", "HPND", "ICU", "IJG", "ISC", "LGPL-2.0-only", "LGPL-2.0-or-later", "LGPL-2.1-only",
+	"LGPL-2.1-or-later", "LGPL-3.0-only", "LGPL-3.0-or-later", "Libpng", "MIT", "MPL-1.1", "MPL-2.0",
+	"MS-PL", "NCSA", "OFL-1.0", "OFL-1.1", "OpenSSL", "PHP-3.01", "PSF-2.0", "Python-2.0", "Ruby",
+	"SGI-B-2.0", "Unicode-DFS-2016", "Unlicense", "Vim", "W3C", "WTFPL", "X11", "Zlib", "ZPL-2.1"
+];
+
+// Exceptions that can go after WITH in a license expression
+pub const SPDX_EXCEPTIONS: &[&str] = &[
+	"Autoconf-exception-3.0", "Bison-exception-2.2", "Classpath-exception-2.0", "GCC-exception-3.1",
+	"LLVM-exception", "OpenSSL-exception", "Qt-GPL-exception-1.0", "Qt-LGPL-exception-1.1", "Font-exception-2.0"
+];
+
+// Every key ypkg reads from a package.yml
+pub const PACKAGE_KEYS: &[&str] = &[
+	"name", "version", "release", "source", "license", "component", "summary", "description", "homepage",
+	"builddeps", "rundeps", "replaces", "patterns", "conflicts", "provides", "permanent", "setup", "build",
+	"install", "check", "profile", "environment", "clang", "extract", "autodep", "emul32", "libsplit",
+	"optimize", "networking", "strip", "lastrip", "ccache", "debug", "devel", "avx2", "mancompress"
+];
@@ -0,0 +1,299 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static REG_PART: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+|[a-z]+").unwrap());
+static REG_SUFFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([a-z]+)(\d*)$").unwrap());
+static REG_PRE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?P<main>.*?\d)[-._~+]?(?P<kw>alpha|beta|preview|pre|rc|p)[-._]?(?P<num>\d*)$").unwrap());
+static REG_SEPS: Lazy<Regex> = Lazy::new(|| Regex::new(r"[-~+_]+").unwrap());
+
+// Solus versions can't contain dashes, pre-releases and patch levels follow
+// the eopkg scheme instead: 1.2.0_alpha1, 1.2.0_beta2, 1.2.0_rc1, 1.2.0_p1
+// Pre-releases sort before the plain version and patch levels after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Suffix {
+	Alpha,
+	Beta,
+	Pre,
+	Rc,
+	Release,
+	Patch
+}
+
+impl Suffix {
+	fn from_keyword(keyword: &str) -> Option<Self> {
+		match keyword {
+			"alpha" => Some(Suffix::Alpha),
+			"beta" => Some(Suffix::Beta),
+			"pre" | "preview" => Some(Suffix::Pre),
+			"rc" => Some(Suffix::Rc),
+			"p" => Some(Suffix::Patch),
+			_ => None
+		}
+	}
+
+	fn keyword(&self) -> &'static str {
+		match self {
+			Suffix::Alpha => "alpha",
+			Suffix::Beta => "beta",
+			Suffix::Pre => "pre",
+			Suffix::Rc => "rc",
+			Suffix::Release => "",
+			Suffix::Patch => "p"
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+	Num(u64),
+	Alpha(String)
+}
+
+impl Ord for Part {
+	fn cmp(&self, other: &Self) -> Ordering {
+		match (self, other) {
+			(Part::Num(a), Part::Num(b)) => a.cmp(b),
+			(Part::Alpha(a), Part::Alpha(b)) => a.cmp(b),
+			// 1.2.1 is newer than 1.2.a
+			(Part::Num(_), Part::Alpha(_)) => Ordering::Greater,
+			(Part::Alpha(_), Part::Num(_)) => Ordering::Less
+		}
+	}
+}
+
+impl PartialOrd for Part {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionError {
+	Empty,
+	HasDash,
+	InvalidChar(char),
+	BadSuffix(String)
+}
+
+impl fmt::Display for VersionError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			VersionError::Empty => write!(f, "version is empty"),
+			VersionError::HasDash => write!(f, "version can't contain dashes"),
+			VersionError::InvalidChar(c) => write!(f, "version can't contain '{}'", c),
+			VersionError::BadSuffix(suffix) => write!(f, "unknown version suffix '{}'", suffix)
+		}
+	}
+}
+
+impl std::error::Error for VersionError {}
+
+#[derive(Debug, Clone)]
+pub struct Version {
+	text: String,
+	parts: Vec<Part>,
+	suffix: Suffix,
+	suffix_num: u64
+}
+
+fn split_parts(main: &str) -> Vec<Part> {
+	REG_PART.find_iter(main)
+		.map(|part| {
+			if let Ok(num) = part.as_str().parse() {
+				Part::Num(num)
+			}
+			else {
+				Part::Alpha(part.as_str().to_string())
+			}
+		})
+		.collect()
+}
+
+impl Version {
+	// Parse a version as found in a package.yml, it must already be a valid Solus version
+	pub fn parse(input: &str) -> Result<Self, VersionError> {
+		let input = input.trim();
+		if input.is_empty() {
+			return Err(VersionError::Empty);
+		}
+
+		if input.contains('-') {
+			return Err(VersionError::HasDash);
+		}
+
+		if let Some(c) = input.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '.' || *c == '_')) {
+			return Err(VersionError::InvalidChar(c));
+		}
+
+		let text = input.to_lowercase();
+		let (main, suffix, suffix_num) = {
+			if let Some(pos) = text.find('_') {
+				let suffix_str = &text[pos + 1..];
+				let captures = REG_SUFFIX.captures(suffix_str).ok_or_else(|| VersionError::BadSuffix(suffix_str.to_string()))?;
+				let suffix = Suffix::from_keyword(&captures[1]).ok_or_else(|| VersionError::BadSuffix(suffix_str.to_string()))?;
+				let suffix_num = captures[2].parse().unwrap_or(0);
+
+				(&text[..pos], suffix, suffix_num)
+			}
+			else {
+				(text.as_str(), Suffix::Release, 0)
+			}
+		};
+
+		let parts = split_parts(main);
+		if parts.is_empty() {
+			return Err(VersionError::Empty);
+		}
+
+		Ok(Version {text: text.clone(), parts, suffix, suffix_num})
+	}
+
+	// Turn whatever upstream uses as a version (v1.2.0-rc.1, 2.0-beta, 1.4~pre3 ...)
+	// into a valid Solus version
+	pub fn normalise(upstream: &str) -> Result<Self, VersionError> {
+		let upstream = upstream.trim().to_lowercase();
+		let upstream = upstream.strip_prefix('v').unwrap_or(&upstream);
+
+		let (main, suffix) = {
+			if let Some(captures) = REG_PRE.captures(upstream) {
+				let suffix = Suffix::from_keyword(&captures["kw"]).unwrap();
+				(captures["main"].to_string(), Some((suffix, captures["num"].to_string())))
+			}
+			else {
+				(upstream.to_string(), None)
+			}
+		};
+
+		let main = REG_SEPS.replace_all(&main, ".").to_string();
+		let main = main.split('.').filter(|part| !part.is_empty()).collect::<Vec<_>>().join(".");
+
+		let normalised = match suffix {
+			Some((suffix, num)) => format!("{}_{}{}", main, suffix.keyword(), num),
+			None => main
+		};
+
+		Self::parse(&normalised)
+	}
+
+	// Version for a snapshot of a git repo, based on the last release if known,
+	// if not 0.0.0 is used so that any proper release will be newer
+	pub fn snapshot(base: Option<&Version>, date: DateTime<Utc>) -> Self {
+		let base = base.map_or_else(|| Self::parse("0.0.0").unwrap(), |base| base.base());
+		let date_str = date.format("%Y%m%d").to_string();
+
+		Version {
+			text: format!("{}_p{}", base.text, date_str),
+			parts: base.parts,
+			suffix: Suffix::Patch,
+			suffix_num: date_str.parse().unwrap()
+		}
+	}
+
+	pub fn is_snapshot(&self) -> bool {
+		self.suffix == Suffix::Patch && self.suffix_num >= 19700101
+	}
+
+	// The same version without any pre-release or patch suffix
+	pub fn base(&self) -> Self {
+		let text = self.text.split('_').next().unwrap().to_string();
+		Version {text, parts: self.parts.clone(), suffix: Suffix::Release, suffix_num: 0}
+	}
+
+	// Parts with trailing zeros removed so that 1.2 and 1.2.0 compare equal
+	fn significant_parts(&self) -> &[Part] {
+		let len = self.parts.iter().rposition(|part| *part != Part::Num(0)).map_or(0, |pos| pos + 1);
+		&self.parts[..len]
+	}
+}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.text)
+	}
+}
+
+impl Ord for Version {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.significant_parts().cmp(other.significant_parts())
+			.then(self.suffix.cmp(&other.suffix))
+			.then(self.suffix_num.cmp(&other.suffix_num))
+	}
+}
+
+impl PartialOrd for Version {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for Version {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Version {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn normalised(upstream: &str) -> String {
+		Version::normalise(upstream).unwrap().to_string()
+	}
+
+	fn v(text: &str) -> Version {
+		Version::parse(text).unwrap()
+	}
+
+	#[test]
+	fn normalises_upstream_versions() {
+		assert_eq!(normalised("v1.2.0"), "1.2.0");
+		assert_eq!(normalised("1.2.0-rc.1"), "1.2.0_rc1");
+		assert_eq!(normalised("2.0-beta"), "2.0_beta");
+		assert_eq!(normalised("1.4~pre3"), "1.4_pre3");
+		assert_eq!(normalised("3.0preview2"), "3.0_pre2");
+		assert_eq!(normalised("1.0_p1"), "1.0_p1");
+		assert_eq!(normalised("2020-05-01"), "2020.05.01");
+		assert_eq!(normalised("1.2+dfsg"), "1.2.dfsg");
+	}
+
+	#[test]
+	fn rejects_what_cant_be_a_version() {
+		assert_eq!(Version::normalise("").unwrap_err(), VersionError::Empty);
+		assert_eq!(Version::normalise("v").unwrap_err(), VersionError::Empty);
+		assert_eq!(Version::normalise("1.0:2").unwrap_err(), VersionError::InvalidChar(':'));
+		assert_eq!(Version::parse("1.0-1").unwrap_err(), VersionError::HasDash);
+		assert_eq!(Version::parse("1.0_foo").unwrap_err(), VersionError::BadSuffix("foo".to_string()));
+	}
+
+	#[test]
+	fn orders_like_eopkg() {
+		let ordered = ["1.0_alpha", "1.0_alpha2", "1.0_beta1", "1.0_pre1", "1.0_rc1", "1.0", "1.0_p1", "1.0.1", "1.2", "1.10"];
+		for pair in ordered.windows(2) {
+			assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+		}
+	}
+
+	#[test]
+	fn trailing_zeros_dont_count() {
+		assert_eq!(v("1.2"), v("1.2.0"));
+		assert!(v("1.2.a") < v("1.2.1"));
+	}
+
+	#[test]
+	fn snapshots_follow_their_base() {
+		let date = "2020-05-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+		let snapshot = Version::snapshot(Some(&v("1.2_rc1")), date);
+		assert_eq!(snapshot.to_string(), "1.2_p20200501");
+		assert!(snapshot.is_snapshot());
+		assert!(snapshot > v("1.2"));
+		assert!(snapshot < v("1.2.1"));
+		assert_eq!(Version::snapshot(None, date).to_string(), "0.0.0_p20200501");
+		assert!(!v("1.0_p1").is_snapshot());
+	}
+}
@@ -0,0 +1,123 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use relm::Sender;
+use tempfile::NamedTempFile;
+
+use crate::error::{Error, Result};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Handed to the work running in the background, so that it can tell how it's
+// going and know when it should stop
+pub struct Job {
+	cancelled: Arc<AtomicBool>,
+	report: Box<dyn Fn(String, Option<f64>) + Send>
+}
+
+fn human_size(bytes: usize) -> String {
+	let mib = bytes as f64 / (1024.0 * 1024.0);
+	if mib >= 1.0 {
+		format!("{:.1} MiB", mib)
+	}
+	else {
+		format!("{} KiB", bytes / 1024)
+	}
+}
+
+impl Job {
+	// A step whose progress can't be measured
+	pub fn step(&self, text: &str) {
+		(self.report)(text.to_string(), None);
+	}
+
+	// A step that is fraction (0 to 1) done
+	pub fn progress(&self, text: &str, fraction: f64) {
+		(self.report)(text.to_string(), Some(fraction));
+	}
+
+	pub fn check_cancelled(&self) -> Result<()> {
+		if self.cancelled.load(Ordering::Relaxed) {
+			Err(Error::Cancelled)
+		}
+		else {
+			Ok(())
+		}
+	}
+
+	// Download url reporting progress and stopping as soon as it's cancelled
+	pub fn download(&self, url: &str) -> Result<Vec<u8>> {
+		let mut buffer = Vec::new();
+		self.download_to(url, &mut buffer)?;
+		Ok(buffer)
+	}
+
+	// Like download, but written to out as it arrives. Returns how much it was.
+	pub fn download_to<W: Write>(&self, url: &str, out: &mut W) -> Result<usize> {
+		let file_name = url.rsplit('/').next().unwrap_or(url).to_string();
+		self.step(&format!("Downloading {}", file_name));
+
+		let mut resp = reqwest::blocking::get(url)?.error_for_status()?;
+		let total = resp.content_length();
+		let mut downloaded = 0;
+		let mut chunk = vec![0; CHUNK_SIZE];
+
+		loop {
+			self.check_cancelled()?;
+			let read = resp.read(&mut chunk)?;
+			if read == 0 {
+				break;
+			}
+
+			out.write_all(&chunk[..read])?;
+			downloaded += read;
+			let fraction = total.map(|total| downloaded as f64 / total as f64);
+			(self.report)(format!("Downloading {} ({})", file_name, human_size(downloaded)), fraction);
+		}
+
+		out.flush()?;
+		Ok(downloaded)
+	}
+}
+
+// A file only needed while a job runs, removed when it's dropped. It's made
+// anew with a random name, so nobody can have a symlink waiting there.
+pub fn temp_file(name: &str) -> Result<NamedTempFile> {
+	Ok(tempfile::Builder::new().prefix("packmak-").suffix(&format!("-{}", name)).tempfile()?)
+}
+
+// Lets the GUI stop a running job
+pub struct CancelHandle {
+	cancelled: Arc<AtomicBool>
+}
+
+impl CancelHandle {
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+}
+
+// Run work in another thread, progress_msg and done_msg make the messages
+// that get sent back to the widget through sender
+pub fn spawn<MSG, T, W, P, D>(sender: Sender<MSG>, progress_msg: P, work: W, done_msg: D) -> CancelHandle
+	where MSG: Send + 'static,
+	      W: FnOnce(&Job) -> T + Send + 'static,
+	      P: Fn(String, Option<f64>) -> MSG + Send + 'static,
+	      D: FnOnce(T) -> MSG + Send + 'static
+{
+	let cancelled = Arc::new(AtomicBool::new(false));
+	let progress_sender = sender.clone();
+	let job = Job {
+		cancelled: cancelled.clone(),
+		// If the window is gone there's nobody to tell, so errors are ignored
+		report: Box::new(move |text, fraction| {let _ = progress_sender.send(progress_msg(text, fraction));})
+	};
+
+	std::thread::spawn(move || {
+		let res = work(&job);
+		let _ = sender.send(done_msg(res));
+	});
+
+	CancelHandle {cancelled}
+}
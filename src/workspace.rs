@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use crate::error::Result;
+use crate::worker::Job;
+
+const PKG_FILE: &str = "package.yml";
+// Enough for both packages/<letter>/<name>/ and one folder per package
+const MAX_DEPTH: usize = 4;
+
+// Only what the sidebar shows, the whole file is read when it's opened
+#[derive(Deserialize)]
+struct PkgSummary {
+	name: String,
+	version: String,
+	release: u16,
+	#[serde(default)]
+	component: String
+}
+
+#[derive(Debug, Clone)]
+pub struct PackageEntry {
+	pub name: String,
+	pub version: String,
+	pub release: u16,
+	pub component: String,
+	pub path: PathBuf,
+	pub modified: Option<SystemTime>
+}
+
+impl PackageEntry {
+	fn read(path: &Path) -> Option<Self> {
+		let summary: PkgSummary = serde_yaml::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+		Some(PackageEntry {
+			name: summary.name,
+			version: summary.version,
+			release: summary.release,
+			component: summary.component,
+			path: path.to_path_buf(),
+			modified: std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+		})
+	}
+
+	pub fn modified_str(&self) -> String {
+		self.modified.map_or("?".to_string(), |modified| DateTime::<Local>::from(modified).format("%Y-%m-%d").to_string())
+	}
+
+	// Case insensitive, on the name and the component
+	pub fn matches(&self, filter: &str) -> bool {
+		let filter = filter.trim().to_lowercase();
+		filter.is_empty() || self.name.to_lowercase().contains(&filter) || self.component.to_lowercase().contains(&filter)
+	}
+}
+
+fn collect(dir: &Path, depth: usize, found: &mut Vec<PathBuf>, job: &Job) -> Result<()> {
+	job.check_cancelled()?;
+	let pkg_file = dir.join(PKG_FILE);
+	if pkg_file.is_file() {
+		found.push(pkg_file);
+		return Ok(());
+	}
+
+	if depth == MAX_DEPTH {
+		return Ok(());
+	}
+
+	let entries = match std::fs::read_dir(dir) {
+		Ok(entries) => entries,
+		Err(_) => return Ok(())
+	};
+	for entry in entries.filter_map(|entry| entry.ok()) {
+		let hidden = entry.file_name().to_string_lossy().starts_with('.');
+		if !hidden && entry.path().is_dir() {
+			collect(&entry.path(), depth + 1, found, job)?;
+		}
+	}
+	Ok(())
+}
+
+// Every package under root, sorted by name. Files that can't be read are
+// left out, the linter is the place to find out what's wrong with them.
+pub fn scan(root: &Path, job: &Job) -> Result<Vec<PackageEntry>> {
+	job.step(&format!("Looking for packages in {}", root.display()));
+	let mut files = Vec::new();
+	collect(root, 0, &mut files, job)?;
+
+	let mut entries = Vec::with_capacity(files.len());
+	for (i, file) in files.iter().enumerate() {
+		job.check_cancelled()?;
+		entries.extend(PackageEntry::read(file));
+		if i % 50 == 0 {
+			job.progress(&format!("Reading packages ({}/{})", i, files.len()), i as f64 / files.len() as f64);
+		}
+	}
+
+	entries.sort_by(|a, b| a.name.cmp(&b.name));
+	Ok(entries)
+}